@@ -0,0 +1,6 @@
+Reads two numbers and prints their sum then counts down from it
+
+,>,                 read a and b
+[-<+>]              move b onto a
+<.                  print the sum
+[.-]                count down to one
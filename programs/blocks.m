@@ -0,0 +1,37 @@
+; Reads numbers until a negative one; prints each, and whether it is zero
+; (1) or not (0), then prints the count of non-negative numbers read.
+
+VAR n
+VAR count
+VAR flag
+
+    LOADI 0
+    STORE count
+    READ n
+    LOADM n
+    WHILE NOTNEG
+        WRITE n
+        IF ZERO
+            LOADI 1
+        ELSE
+            LOADI 0
+        ENDIF
+        STORE flag
+        WRITE flag
+        LOADM count
+        ADDI 1
+        STORE count
+        READ n
+        LOADM n
+    ENDW
+
+    ; count down from count to 1
+    LOADM count
+    IF POS
+        REPEAT
+            STORE n
+            WRITE n
+            SUBI 1
+        UNTIL ZERO
+    ENDIF
+    HALT
@@ -0,0 +1,10 @@
+; Exits with status 0 if the input is even and 1 if it is odd.
+VAR n
+
+    READ   n
+    LOADM  n
+    MODI   2
+    JZ     even
+    HALT   1
+even:
+    HALT
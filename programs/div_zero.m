@@ -0,0 +1,10 @@
+; Divides by a variable that is still zero to demonstrate a runtime fault report
+VAR x
+VAR zero
+
+LOADI 7
+STORE x
+LOADM x
+DIVM  zero      ; faults: zero was never written
+WRITE x
+HALT
@@ -0,0 +1,10 @@
+; x = 2 * x, written the long way; try: cargo run -- programs/fragments/double.m --superopt
+VAR x
+VAR t
+LOADM x
+STORE t
+LOADM t
+ADDM  t
+STORE x
+LOADI 0
+ADDM  x
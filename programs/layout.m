@@ -0,0 +1,22 @@
+; Data first, code at a fixed address, entry point in the middle.
+.data
+VAR total
+VAR step
+
+.text
+ORG 20
+helper:
+    LOADM  total
+    ADDM   step
+    STORE  total
+    WRITE  total
+    HALT
+
+START main
+ORG 40
+main:
+    LOADI  5
+    STORE  total
+    LOADI  7
+    STORE  step
+    JMP    helper
@@ -0,0 +1,23 @@
+; Benchmark: nested countdown loops, about 4 million instructions.
+; Run with --stats to see instructions per second.
+VAR outer
+VAR inner
+
+    LOADI  99
+    MULI   10
+    STORE  outer
+next:
+    LOADI  99
+    MULI   10
+    STORE  inner
+spin:
+    LOADM  inner
+    SUBI   1
+    STORE  inner
+    JNZ    spin
+    LOADM  outer
+    SUBI   1
+    STORE  outer
+    JNZ    next
+    WRITE  outer
+    HALT
@@ -0,0 +1,21 @@
+; Exercises every optimizer rewrite; run with --optimize to see the savings
+VAR n
+VAR total
+
+READ   n
+LOADI  2
+ADDI   3        ; folds into LOADI 5
+MULI   1        ; no-op
+STORE  total
+LOADM  total    ; redundant reload after STORE
+ADDM   n
+STORE  total
+JMP    hop      ; threads through to done
+
+hop:
+JMP    done
+WRITE  n        ; unreachable
+
+done:
+WRITE  total
+HALT
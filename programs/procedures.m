@@ -0,0 +1,27 @@
+; Prints a*a and b*b using a SQUARE procedure
+
+VAR a
+VAR b
+VAR n
+VAR result
+
+    READ a
+    READ b
+
+    LOADM a
+    STORE n
+    CALL square
+    WRITE result
+
+    LOADM b
+    STORE n
+    CALL square
+    WRITE result
+    HALT
+
+PROC square
+    LOADM n
+    MULM n
+    STORE result
+    RET
+ENDP
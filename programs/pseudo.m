@@ -0,0 +1,31 @@
+; Pseudo-instructions: reads a and b, swaps them, and prints |a - b|,
+; then counts b down to zero.
+
+VAR a
+VAR b
+VAR copy
+
+    READ a
+    READ b
+    SWAP a, b
+    WRITE a
+    WRITE b
+    LOADM a
+    SUBM b
+    ABS
+    STORE copy
+    WRITE copy
+    MOV copy, a
+    LOADM copy
+    NEG
+    WRITEA
+    LOADM b
+    WHILE POS
+        DEC b
+        WRITE b
+        LOADM b
+    ENDW
+    CLR a
+    INC a
+    WRITE a
+    HALT
@@ -0,0 +1,29 @@
+; Computes (a + b) * (a - b) with temporaries whose lifetimes do not overlap.
+; Run with --pack-vars --listing to see which variables share a cell.
+VAR a
+VAR b
+VAR sum
+VAR diff
+VAR result
+VAR scratch
+
+READ   a
+READ   b
+
+LOADM  a
+ADDM   b
+STORE  sum
+
+LOADM  a
+SUBM   b
+STORE  diff
+
+LOADM  sum
+MULM   diff
+STORE  result
+WRITE  result
+
+LOADI  1
+STORE  scratch
+WRITE  scratch
+HALT
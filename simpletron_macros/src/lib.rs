@@ -0,0 +1,152 @@
+//! `sml!`: assembles mnemonic source at compile time.
+//!
+//! Statements are separated by `;`, and a label (`loop:`) ends a statement
+//! on its own. The macro expands to a `[u16; N]` word image, so
+//! `const PROGRAM: [u16; 3] = sml! { VAR n; READ n; WRITE n; HALT };`
+//! needs no run-time assembly. Assembly errors are reported as compile
+//! errors on the offending tokens; lint warnings are not reported.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2, TokenTree};
+use quote::quote;
+use simpletron_rust::{
+    assembler::{self, parser::mnemonic_parser::MnemonicParser},
+    vm::error::SimpletronError,
+};
+
+/// One source line and the tokens it was written with.
+struct Statement {
+    text: String,
+    tokens: Vec<TokenTree>,
+}
+
+impl Statement {
+    fn new(tokens: Vec<TokenTree>) -> Self {
+        let mut text = String::new();
+        let mut glued = true;
+        for token in &tokens {
+            let piece = token.to_string();
+            let punct = match token {
+                TokenTree::Punct(punct) => Some(punct.as_char()),
+                _ => None,
+            };
+            if !glued && !matches!(punct, Some(':' | ',')) {
+                text.push(' ');
+            }
+            text.push_str(&piece);
+            // `.data` is written as a `.` followed by an identifier.
+            glued = punct == Some('.');
+        }
+        Self { text, tokens }
+    }
+
+    /// The first token spelled `name`, or the whole statement.
+    fn token(&self, name: &str) -> TokenStream2 {
+        match self.tokens.iter().find(|token| token.to_string() == name) {
+            Some(token) => token.clone().into(),
+            None => self.tokens.iter().cloned().collect(),
+        }
+    }
+}
+
+fn statements(input: TokenStream2) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut current: Vec<TokenTree> = Vec::new();
+
+    for token in input {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ';' => {
+                if !current.is_empty() {
+                    statements.push(Statement::new(std::mem::take(&mut current)));
+                }
+            }
+            TokenTree::Punct(punct) if punct.as_char() == ':' && current.len() == 1 => {
+                current.push(token);
+                statements.push(Statement::new(std::mem::take(&mut current)));
+            }
+            _ => current.push(token),
+        }
+    }
+    if !current.is_empty() {
+        statements.push(Statement::new(current));
+    }
+    statements
+}
+
+/// Tokens an assembler error is about, or `None` for the whole program.
+fn error_tokens(error: &SimpletronError, statements: &[Statement]) -> Option<TokenStream2> {
+    match error {
+        SimpletronError::InvalidAddress { line }
+        | SimpletronError::InvalidPseudoOp { line, .. }
+        | SimpletronError::CompileError { line, .. } => statements
+            .get(line.checked_sub(1)?)
+            .map(|statement| statement.tokens.iter().cloned().collect()),
+        SimpletronError::DeniedLints(warnings) => {
+            let line = warnings.first()?.line;
+            error_tokens(&SimpletronError::InvalidAddress { line }, statements)
+        }
+        SimpletronError::UnknownLabel(name) | SimpletronError::UnknownVariable(name) => statements
+            .iter()
+            .find(|statement| {
+                statement
+                    .tokens
+                    .iter()
+                    .skip(1)
+                    .any(|t| t.to_string() == *name)
+            })
+            .map(|statement| statement.token(name)),
+        SimpletronError::DuplicateLabel(name) | SimpletronError::DuplicateVariable(name) => {
+            statements
+                .iter()
+                .filter(|statement| statement.tokens.iter().any(|t| t.to_string() == *name))
+                .nth(1)
+                .map(|statement| statement.token(name))
+        }
+        _ => None,
+    }
+}
+
+fn compile_error(tokens: Option<TokenStream2>, error: &SimpletronError) -> TokenStream {
+    // Line numbers count statements, not lines of the Rust file, so they
+    // are left to the span.
+    let message = match error {
+        SimpletronError::InvalidPseudoOp { message, .. }
+        | SimpletronError::CompileError { message, .. } => message.clone(),
+        _ => error.to_string(),
+    };
+    let error = match tokens {
+        Some(tokens) => syn::Error::new_spanned(tokens, message),
+        None => syn::Error::new(Span::call_site(), message),
+    };
+    error.to_compile_error().into()
+}
+
+/// Assembles the mnemonic program in the macro body into a `[u16; N]`.
+#[proc_macro]
+pub fn sml(input: TokenStream) -> TokenStream {
+    let statements = statements(input.into());
+
+    // Parse line by line first, so a bad line points at its own tokens.
+    for statement in &statements {
+        if let Err(error) = MnemonicParser::parse_line(&statement.text) {
+            let tokens = match &error {
+                SimpletronError::InvalidInstruction(name)
+                | SimpletronError::InvalidOperand(name) => statement.token(name),
+                _ => statement.tokens.iter().cloned().collect(),
+            };
+            return compile_error(Some(tokens), &error);
+        }
+    }
+
+    let source: String = statements
+        .iter()
+        .map(|statement| format!("{}\n", statement.text))
+        .collect();
+    match assembler::assemble(&source) {
+        Ok(words) => {
+            let words = words.into_iter().map(Literal::u16_suffixed);
+            quote!([#(#words),*]).into()
+        }
+        Err(error) => compile_error(error_tokens(&error, &statements), &error),
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::assembler::{
+    analysis::{CodeView, VarAccess, is_extension, is_memory_op, liveness, var_access},
+    instruction::Operand,
+    layout::Directive,
+    parser::mnemonic_parser::ParsedLine,
+};
+
+/// Assignment of variables to shared data cells.
+#[derive(Debug, Clone, Default)]
+pub struct Packing {
+    /// Cell index (relative to the start of the data area) per variable.
+    pub slots: HashMap<String, usize>,
+}
+
+impl Packing {
+    pub fn slot_count(&self) -> usize {
+        self.slots.values().max().map_or(0, |max| max + 1)
+    }
+}
+
+/// Lets variables whose live ranges never overlap share a cell.
+///
+/// Variables named in `separate` always keep a cell of their own. Returns
+/// `None` when the program addresses memory numerically, patches its own
+/// code or uses an extension instruction, because such accesses may alias
+/// any variable.
+pub fn pack_variables(
+    parsed: &[ParsedLine],
+    line_numbers: &[usize],
+    separate: &[String],
+) -> Option<Packing> {
+    let code = CodeView::new(parsed, line_numbers);
+
+    let aliased_access = code.instructions.iter().any(|instr| {
+        is_extension(instr.mnemonic)
+            || is_memory_op(instr.mnemonic)
+                && matches!(
+                    instr.operand,
+                    Some(Operand::Immediate(_) | Operand::Label(_))
+                )
+    });
+    let fixed_layout = parsed.iter().any(|line| {
+        matches!(
+            line,
+            ParsedLine::Directive(Directive::Org(_) | Directive::Section(_))
+        )
+    });
+    if aliased_access || fixed_layout {
+        return None;
+    }
+
+    let interference = interference(&code);
+
+    // Variables whose address is taken may be read through self-modifying
+    // code, so they keep a cell of their own.
+    let pinned: HashSet<&str> = code
+        .instructions
+        .iter()
+        .filter_map(|instr| match var_access(instr) {
+            Some(VarAccess::Address(name)) => Some(name),
+            _ => None,
+        })
+        .chain(separate.iter().map(String::as_str))
+        .collect();
+
+    let mut packing = Packing::default();
+    let mut occupants: Vec<Vec<&str>> = Vec::new();
+
+    for line in parsed {
+        let ParsedLine::Variable(name) = line else {
+            continue;
+        };
+        let name = name.as_str();
+
+        let slot = if pinned.contains(name) {
+            None
+        } else {
+            occupants.iter().position(|cell| {
+                cell.iter().all(|other| {
+                    !pinned.contains(other)
+                        && !interference
+                            .get(name)
+                            .is_some_and(|set| set.contains(other))
+                })
+            })
+        };
+
+        let slot = slot.unwrap_or_else(|| {
+            occupants.push(Vec::new());
+            occupants.len() - 1
+        });
+        occupants[slot].push(name);
+        packing.slots.insert(name.to_string(), slot);
+    }
+
+    Some(packing)
+}
+
+/// Two variables interfere when one is written while the other is live,
+/// or when both hold their initial (zero) value at the entry point.
+fn interference<'a>(code: &CodeView<'a>) -> HashMap<&'a str, HashSet<&'a str>> {
+    let live = liveness(code);
+    let mut graph: HashMap<&str, HashSet<&str>> = HashMap::new();
+
+    let mut add_edge = |a: &'a str, b: &'a str| {
+        if a != b {
+            graph.entry(a).or_default().insert(b);
+            graph.entry(b).or_default().insert(a);
+        }
+    };
+
+    if let Some(entry) = live.live_in.first() {
+        for a in entry {
+            for b in entry {
+                add_edge(a, b);
+            }
+        }
+    }
+
+    for (index, instr) in code.instructions.iter().enumerate() {
+        if let Some(VarAccess::Write(name)) = var_access(instr) {
+            for other in &live.live_out[index] {
+                add_edge(name, other);
+            }
+        }
+    }
+
+    graph
+}
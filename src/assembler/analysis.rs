@@ -0,0 +1,259 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::assembler::{
+    instruction::{AsmInstruction, Mnemonic, Operand},
+    layout::Directive,
+    parser::mnemonic_parser::ParsedLine,
+};
+
+/// Variables live on entry to and exit from each instruction.
+pub struct Liveness<'a> {
+    pub live_in: Vec<HashSet<&'a str>>,
+    pub live_out: Vec<HashSet<&'a str>>,
+}
+
+/// How an instruction touches a named variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarAccess<'a> {
+    Read(&'a str),
+    Write(&'a str),
+    /// The variable's address is used as an immediate (`LOADI x`).
+    Address(&'a str),
+}
+
+/// Instruction-level view of a parsed program, with labels resolved to
+/// instruction indices. Index `len()` stands for "past the last instruction".
+pub struct CodeView<'a> {
+    pub instructions: Vec<&'a AsmInstruction>,
+    pub lines: Vec<usize>,
+    pub labels: HashMap<&'a str, usize>,
+    /// Instructions the program overwrites at run time (`STORE label`).
+    pub patched: HashSet<usize>,
+    /// Instructions whose address is used as a value (`ADDI label`), and
+    /// so may become the target of a patched jump.
+    pub address_taken: Vec<usize>,
+    /// Instruction execution starts at: the `START` label, or the first one.
+    pub entry: usize,
+}
+
+impl<'a> CodeView<'a> {
+    pub fn new(parsed: &'a [ParsedLine], line_numbers: &[usize]) -> Self {
+        let mut instructions = Vec::new();
+        let mut lines = Vec::new();
+        let mut labels = HashMap::new();
+        let mut start = None;
+
+        for (line, number) in parsed.iter().zip(line_numbers) {
+            match line {
+                ParsedLine::Label(name) => {
+                    labels.insert(name.as_str(), instructions.len());
+                }
+                ParsedLine::Instruction(instr) => {
+                    instructions.push(instr);
+                    lines.push(*number);
+                }
+                ParsedLine::Directive(Directive::Start(label)) => start = Some(label.as_str()),
+                ParsedLine::Variable(_) | ParsedLine::Pseudo(_) | ParsedLine::Directive(_) => {}
+            }
+        }
+        let entry = start
+            .and_then(|label| labels.get(label).copied())
+            .unwrap_or(0);
+
+        let mut patched = HashSet::new();
+        let mut address_taken = Vec::new();
+        for instr in &instructions {
+            if let Some(Operand::Label(name)) = &instr.operand
+                && !is_jump(instr.mnemonic)
+                && let Some(&target) = labels.get(name.as_str())
+            {
+                match instr.mnemonic {
+                    Mnemonic::Store | Mnemonic::Read => {
+                        patched.insert(target);
+                    }
+                    _ if !is_memory_op(instr.mnemonic) && !address_taken.contains(&target) => {
+                        address_taken.push(target);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Self {
+            instructions,
+            lines,
+            labels,
+            patched,
+            address_taken,
+            entry,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// Index an instruction jumps to, if it is a jump.
+    pub fn jump_target(&self, index: usize) -> Option<usize> {
+        let instr = self.instructions[index];
+        if !is_jump(instr.mnemonic) {
+            return None;
+        }
+
+        match &instr.operand {
+            Some(Operand::Label(name)) => self.labels.get(name.as_str()).copied(),
+            Some(Operand::Immediate(addr)) => Some((*addr).min(self.len())),
+            _ => None,
+        }
+    }
+
+    /// Possible next instruction indices after executing `index`. A patched
+    /// jump or `HALT` is assumed to stay a control transfer, which may now
+    /// go to any instruction whose address the program takes.
+    pub fn successors(&self, index: usize) -> Vec<usize> {
+        let instr = self.instructions[index];
+        let mut next = Vec::new();
+
+        if falls_through(instr.mnemonic) {
+            next.push(index + 1);
+        }
+        if let Some(target) = self.jump_target(index)
+            && !next.contains(&target)
+        {
+            next.push(target);
+        }
+        if self.patched.contains(&index) && !falls_through(instr.mnemonic) {
+            for &target in &self.address_taken {
+                if !next.contains(&target) {
+                    next.push(target);
+                }
+            }
+        }
+
+        next
+    }
+
+    /// Predecessor lists, the inverse of [`CodeView::successors`].
+    pub fn predecessors(&self) -> Vec<Vec<usize>> {
+        let mut preds = vec![Vec::new(); self.len() + 1];
+        for index in 0..self.len() {
+            for succ in self.successors(index) {
+                preds[succ].push(index);
+            }
+        }
+        preds
+    }
+
+    /// Instructions reachable from the entry point.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.len()];
+        let mut stack = vec![self.entry];
+
+        while let Some(index) = stack.pop() {
+            if index >= self.len() || seen[index] {
+                continue;
+            }
+            seen[index] = true;
+            stack.extend(self.successors(index));
+        }
+
+        seen
+    }
+}
+
+pub fn is_jump(mnemonic: Mnemonic) -> bool {
+    matches!(
+        mnemonic,
+        Mnemonic::Jump
+            | Mnemonic::JumpIfZero
+            | Mnemonic::JumpIfNegative
+            | Mnemonic::JumpIfNotZero
+            | Mnemonic::JumpIfGreaterThanZero
+    )
+}
+
+/// Whether the operand of this instruction is a data address.
+pub fn is_memory_op(mnemonic: Mnemonic) -> bool {
+    matches!(
+        mnemonic,
+        Mnemonic::Read
+            | Mnemonic::Write
+            | Mnemonic::LoadM
+            | Mnemonic::Store
+            | Mnemonic::AddM
+            | Mnemonic::SubM
+            | Mnemonic::DivM
+            | Mnemonic::ModM
+            | Mnemonic::MulM
+    )
+}
+
+/// Whether the instruction is one only its instruction set knows, so its
+/// effect on control flow and memory cannot be analysed.
+pub fn is_extension(mnemonic: Mnemonic) -> bool {
+    matches!(mnemonic, Mnemonic::Extension(_))
+}
+
+/// Whether control can continue to the next instruction.
+pub fn falls_through(mnemonic: Mnemonic) -> bool {
+    !matches!(mnemonic, Mnemonic::Jump | Mnemonic::Halt)
+}
+
+pub fn var_access(instr: &AsmInstruction) -> Option<VarAccess<'_>> {
+    let Some(Operand::Variable(name)) = &instr.operand else {
+        return None;
+    };
+
+    Some(match instr.mnemonic {
+        Mnemonic::Read | Mnemonic::Store => VarAccess::Write(name),
+        Mnemonic::LoadI
+        | Mnemonic::AddI
+        | Mnemonic::SubI
+        | Mnemonic::DivI
+        | Mnemonic::ModI
+        | Mnemonic::MulI => VarAccess::Address(name),
+        _ => VarAccess::Read(name),
+    })
+}
+
+/// Backward liveness over the control-flow graph. Nothing is live past the
+/// last instruction.
+pub fn liveness<'a>(code: &CodeView<'a>) -> Liveness<'a> {
+    let mut live_in: Vec<HashSet<&str>> = vec![HashSet::new(); code.len() + 1];
+    let mut live_out: Vec<HashSet<&str>> = vec![HashSet::new(); code.len()];
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in (0..code.len()).rev() {
+            let mut out = HashSet::new();
+            for succ in code.successors(index) {
+                out.extend(live_in[succ].iter().copied());
+            }
+
+            let mut inn = out.clone();
+            match var_access(code.instructions[index]) {
+                Some(VarAccess::Write(name)) => {
+                    inn.remove(name);
+                }
+                Some(VarAccess::Read(name)) => {
+                    inn.insert(name);
+                }
+                _ => {}
+            }
+
+            if inn != live_in[index] {
+                live_in[index] = inn;
+                changed = true;
+            }
+            live_out[index] = out;
+        }
+    }
+
+    live_in.truncate(code.len());
+    Liveness { live_in, live_out }
+}
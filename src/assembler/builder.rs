@@ -0,0 +1,244 @@
+use std::collections::HashSet;
+
+use crate::{
+    assembler::{
+        AssembledProgram, AssemblerOptions,
+        instruction::{AsmInstruction, Mnemonic, Operand},
+        parser::mnemonic_parser::ParsedLine,
+    },
+    vm::error::SimpletronError,
+};
+
+/// Builds a program from Rust code instead of source text. Methods are
+/// named after the mnemonics and chain, so `.var("n").read("n").halt()` is
+/// the program `VAR n / READ n / HALT`.
+///
+/// The program goes through the same passes as assembled text, and
+/// [`ProgramBuilder::source`] gives the equivalent source. Operands naming
+/// undeclared symbols are rejected by [`ProgramBuilder::build`].
+#[derive(Debug, Clone, Default)]
+pub struct ProgramBuilder {
+    lines: Vec<ParsedLine>,
+    options: AssemblerOptions,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options used by [`ProgramBuilder::build`].
+    pub fn options(&mut self, options: AssemblerOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    pub fn var(&mut self, name: &str) -> &mut Self {
+        self.lines.push(ParsedLine::Variable(name.to_string()));
+        self
+    }
+
+    pub fn label(&mut self, name: &str) -> &mut Self {
+        self.lines.push(ParsedLine::Label(name.to_string()));
+        self
+    }
+
+    pub fn instruction(&mut self, instruction: AsmInstruction) -> &mut Self {
+        self.lines.push(ParsedLine::Instruction(instruction));
+        self
+    }
+
+    fn emit(&mut self, mnemonic: Mnemonic, operand: Option<Operand>) -> &mut Self {
+        self.instruction(AsmInstruction { mnemonic, operand })
+    }
+
+    fn memory(&mut self, mnemonic: Mnemonic, var: &str) -> &mut Self {
+        self.emit(mnemonic, Some(Operand::Variable(var.to_string())))
+    }
+
+    fn immediate(&mut self, mnemonic: Mnemonic, value: usize) -> &mut Self {
+        self.emit(mnemonic, Some(Operand::Immediate(value)))
+    }
+
+    fn jump(&mut self, mnemonic: Mnemonic, label: &str) -> &mut Self {
+        self.emit(mnemonic, Some(Operand::Label(label.to_string())))
+    }
+
+    pub fn read(&mut self, var: &str) -> &mut Self {
+        self.memory(Mnemonic::Read, var)
+    }
+
+    pub fn readi(&mut self) -> &mut Self {
+        self.emit(Mnemonic::ReadI, None)
+    }
+
+    pub fn write(&mut self, var: &str) -> &mut Self {
+        self.memory(Mnemonic::Write, var)
+    }
+
+    pub fn writea(&mut self) -> &mut Self {
+        self.emit(Mnemonic::WriteAcc, None)
+    }
+
+    pub fn loadm(&mut self, var: &str) -> &mut Self {
+        self.memory(Mnemonic::LoadM, var)
+    }
+
+    pub fn loadi(&mut self, value: usize) -> &mut Self {
+        self.immediate(Mnemonic::LoadI, value)
+    }
+
+    pub fn store(&mut self, var: &str) -> &mut Self {
+        self.memory(Mnemonic::Store, var)
+    }
+
+    pub fn addm(&mut self, var: &str) -> &mut Self {
+        self.memory(Mnemonic::AddM, var)
+    }
+
+    pub fn subm(&mut self, var: &str) -> &mut Self {
+        self.memory(Mnemonic::SubM, var)
+    }
+
+    pub fn mulm(&mut self, var: &str) -> &mut Self {
+        self.memory(Mnemonic::MulM, var)
+    }
+
+    pub fn divm(&mut self, var: &str) -> &mut Self {
+        self.memory(Mnemonic::DivM, var)
+    }
+
+    pub fn modm(&mut self, var: &str) -> &mut Self {
+        self.memory(Mnemonic::ModM, var)
+    }
+
+    pub fn addi(&mut self, value: usize) -> &mut Self {
+        self.immediate(Mnemonic::AddI, value)
+    }
+
+    pub fn subi(&mut self, value: usize) -> &mut Self {
+        self.immediate(Mnemonic::SubI, value)
+    }
+
+    pub fn muli(&mut self, value: usize) -> &mut Self {
+        self.immediate(Mnemonic::MulI, value)
+    }
+
+    pub fn divi(&mut self, value: usize) -> &mut Self {
+        self.immediate(Mnemonic::DivI, value)
+    }
+
+    pub fn modi(&mut self, value: usize) -> &mut Self {
+        self.immediate(Mnemonic::ModI, value)
+    }
+
+    pub fn jmp(&mut self, label: &str) -> &mut Self {
+        self.jump(Mnemonic::Jump, label)
+    }
+
+    pub fn jn(&mut self, label: &str) -> &mut Self {
+        self.jump(Mnemonic::JumpIfNegative, label)
+    }
+
+    pub fn jz(&mut self, label: &str) -> &mut Self {
+        self.jump(Mnemonic::JumpIfZero, label)
+    }
+
+    pub fn jnz(&mut self, label: &str) -> &mut Self {
+        self.jump(Mnemonic::JumpIfNotZero, label)
+    }
+
+    pub fn jg(&mut self, label: &str) -> &mut Self {
+        self.jump(Mnemonic::JumpIfGreaterThanZero, label)
+    }
+
+    pub fn halt(&mut self) -> &mut Self {
+        self.emit(Mnemonic::Halt, None)
+    }
+
+    /// `HALT status`.
+    pub fn halt_with(&mut self, status: usize) -> &mut Self {
+        self.immediate(Mnemonic::Halt, status)
+    }
+
+    /// Equivalent mnemonic source, one line per builder call.
+    pub fn source(&self) -> String {
+        self.rendered()
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    fn rendered(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .map(|line| match line {
+                ParsedLine::Label(name) => format!("{}:", name),
+                ParsedLine::Variable(name) => format!("VAR {}", name),
+                ParsedLine::Instruction(instr) => format!("    {}", instr),
+                ParsedLine::Pseudo(_) | ParsedLine::Directive(_) => {
+                    unreachable!("the builder only emits labels, variables and instructions")
+                }
+            })
+            .collect()
+    }
+
+    /// Every operand must name a declared variable or label. A memory
+    /// operand may name a label, as in source text, to patch code.
+    fn check_symbols(&self) -> Result<(), SimpletronError> {
+        let labels: HashSet<&str> = self
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                ParsedLine::Label(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        let vars: HashSet<&str> = self
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                ParsedLine::Variable(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        for line in &self.lines {
+            let ParsedLine::Instruction(instr) = line else {
+                continue;
+            };
+            match &instr.operand {
+                Some(Operand::Label(name)) if !labels.contains(name.as_str()) => {
+                    return Err(SimpletronError::UnknownLabel(name.clone()));
+                }
+                Some(Operand::Variable(name))
+                    if !vars.contains(name.as_str()) && !labels.contains(name.as_str()) =>
+                {
+                    return Err(SimpletronError::UnknownVariable(name.clone()));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Assembles the program as if it were the text of [`ProgramBuilder::source`].
+    pub fn build(&self) -> Result<AssembledProgram, SimpletronError> {
+        self.check_symbols()?;
+
+        let rendered = self.rendered();
+        let source_lines: Vec<&str> = rendered.iter().map(String::as_str).collect();
+        let line_numbers = (1..=self.lines.len()).collect();
+        super::assemble_parsed(
+            self.lines.clone(),
+            line_numbers,
+            &source_lines,
+            &self.options,
+        )
+    }
+
+    /// Word image of the built program.
+    pub fn words(&self) -> Result<Vec<u16>, SimpletronError> {
+        Ok(self.build()?.words)
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt::Write;
+
+use crate::{
+    assembler::{
+        self, AssembledProgram, AssemblerOptions,
+        lint::{Lint, LintLevel},
+    },
+    vm::{debug_info::DebugInfo, error::SimpletronError},
+};
+
+/// Mnemonic source produced by a compiler front end, with the line of the
+/// original source each generated line came from (0 for none).
+#[derive(Debug, Clone, Default)]
+pub struct GeneratedSource {
+    pub lines: Vec<(String, usize)>,
+}
+
+impl GeneratedSource {
+    pub fn push(&mut self, text: impl Into<String>, origin: usize) {
+        self.lines.push((text.into(), origin));
+    }
+
+    pub fn source(&self) -> String {
+        let mut out = String::new();
+        for (text, _) in &self.lines {
+            writeln!(out, "{}", text).unwrap();
+        }
+        out
+    }
+
+    /// Original source line for a 1-based line of [`GeneratedSource::source`].
+    pub fn origin(&self, generated_line: usize) -> Option<usize> {
+        self.lines
+            .get(generated_line.checked_sub(1)?)
+            .map(|(_, line)| *line)
+            .filter(|line| *line > 0)
+    }
+
+    /// Assembles the generated source, pointing the debug info at
+    /// `original` instead of the generated mnemonics. Lints are about
+    /// hand-written code, so they are switched off.
+    pub fn assemble(&self, original: &str) -> Result<AssembledProgram, SimpletronError> {
+        let mut options = AssemblerOptions::default();
+        for lint in Lint::ALL {
+            options.lints.set(lint, LintLevel::Allow);
+        }
+        let mut program = assembler::assemble_with(&self.source(), &options)?;
+
+        let source_lines: Vec<&str> = original.lines().collect();
+        let mut debug_info = DebugInfo::new();
+        for address in 0..program.words.len() {
+            if let Some(generated) = program.debug_info.lookup(address)
+                && let Some(line) = self.origin(generated.number)
+                && let Some(text) = source_lines.get(line - 1)
+            {
+                debug_info.insert(address, line, text);
+            }
+        }
+        program.debug_info = debug_info;
+
+        Ok(program)
+    }
+}
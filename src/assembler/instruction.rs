@@ -1,49 +1,32 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Mnemonic {
-    // I/O
-    Read,
-    Write,
-    WriteAcc,
-    ReadI,
+use std::fmt;
 
-    // Memory
-    LoadM,
-    Store,
-    LoadI,
+/// Assembly instructions name the operations the machine executes; the
+/// instruction set decides how each is spelled and numbered.
+pub use crate::vm::operation::Opcode as Mnemonic;
 
-    // Arithmetic (memory)
-    AddM,
-    SubM,
-    DivM,
-    ModM,
-    MulM,
-
-    // Arithmetic (immediate)
-    AddI,
-    SubI,
-    DivI,
-    ModI,
-    MulI,
-
-    // Control flow
-    Jump,
-    JumpIfNegative,
-    JumpIfZero,
-    JumpIfNotZero,
-    JumpIfGreaterThanZero,
-
-    Halt,
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AsmInstruction {
     pub mnemonic: Mnemonic,
     pub operand: Option<Operand>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Operand {
     Immediate(usize),
     Label(String),
     Variable(String),
 }
+
+impl fmt::Display for AsmInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.operand {
+            Some(Operand::Immediate(value)) => {
+                write!(f, "{:<6} {}", self.mnemonic.mnemonic(), value)
+            }
+            Some(Operand::Label(name) | Operand::Variable(name)) => {
+                write!(f, "{:<6} {}", self.mnemonic.mnemonic(), name)
+            }
+            None => write!(f, "{}", self.mnemonic.mnemonic()),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use crate::{
+    assembler::{LabelTable, VarTable, allocator::Packing, parser::mnemonic_parser::ParsedLine},
+    vm::error::SimpletronError,
+};
+
+/// Number of memory cells a program can be laid out in.
+pub const MEMORY_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Text,
+    Data,
+}
+
+/// Layout directives. Unlike pseudo-ops they stay in the parsed program,
+/// since they only matter when addresses are assigned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Directive {
+    /// `ORG addr`: continue the current section at a fixed address.
+    Org(usize),
+    /// `.text` / `.data`: switch section.
+    Section(Section),
+    /// `START label`: entry point.
+    Start(String),
+}
+
+impl Directive {
+    /// Parses a directive, or returns `None` if `parts` is not one.
+    pub fn parse(parts: &[&str]) -> Result<Option<Directive>, SimpletronError> {
+        let directive = match parts {
+            [".text"] => Directive::Section(Section::Text),
+            [".data"] => Directive::Section(Section::Data),
+            ["ORG", address] => Directive::Org(
+                address
+                    .parse()
+                    .map_err(|_| SimpletronError::InvalidOperand(address.to_string()))?,
+            ),
+            ["START", label] => Directive::Start(label.to_string()),
+            [".text" | ".data" | "ORG" | "START", ..] => {
+                return Err(SimpletronError::InvalidInstructionLine);
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(directive))
+    }
+}
+
+/// Addresses assigned by the first pass.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub labels: LabelTable,
+    pub vars: VarTable,
+    /// Address of each instruction, in source order.
+    pub addresses: Vec<usize>,
+    pub entry: usize,
+}
+
+impl Layout {
+    /// Places encoded instructions (in source order) into a memory image
+    /// that starts at address 0. Cells between them are left zero.
+    pub fn image(&self, code: &[u16]) -> Vec<u16> {
+        let len = self.addresses.iter().max().map_or(0, |last| last + 1);
+        let mut image = vec![0; len];
+        for (address, word) in self.addresses.iter().zip(code) {
+            image[*address] = *word;
+        }
+        image
+    }
+}
+
+enum Item<'a> {
+    Org(usize),
+    Label(&'a str),
+    Instruction(usize),
+    Variable(&'a str),
+}
+
+fn error(line: usize, message: impl Into<String>) -> SimpletronError {
+    SimpletronError::InvalidPseudoOp {
+        line,
+        message: message.into(),
+    }
+}
+
+/// Cells claimed so far, with a description of who claimed them.
+struct Placement {
+    owners: HashMap<usize, String>,
+}
+
+impl Placement {
+    fn claim(&mut self, address: usize, owner: String) -> Result<(), SimpletronError> {
+        if address >= MEMORY_SIZE {
+            return Err(SimpletronError::ProgramTooLarge {
+                size: address + 1,
+                capacity: MEMORY_SIZE,
+            });
+        }
+        if let Some(first) = self.owners.get(&address) {
+            return Err(SimpletronError::Overlap {
+                address,
+                first: first.clone(),
+                second: owner,
+            });
+        }
+        self.owners.insert(address, owner);
+        Ok(())
+    }
+}
+
+/// Assigns addresses to labels, variables and instructions.
+///
+/// Sections are laid out in the order they first appear, each continuing
+/// where the previous one ended unless `ORG` moves it. Without directives
+/// this is code from address 0 followed by the variables. `VAR` lines
+/// always belong to the data section, wherever they are written.
+pub fn layout(
+    lines: &[ParsedLine],
+    line_numbers: &[usize],
+    packing: Option<&Packing>,
+) -> Result<Layout, SimpletronError> {
+    let mut text: Vec<(Item, usize)> = Vec::new();
+    let mut data: Vec<(Item, usize)> = Vec::new();
+    let mut order: Vec<Section> = Vec::new();
+    let mut section = Section::Text;
+    let mut start: Option<(&str, usize)> = None;
+    let mut instructions = 0;
+
+    for (line, &number) in lines.iter().zip(line_numbers) {
+        let item = match line {
+            ParsedLine::Directive(Directive::Section(next)) => {
+                section = *next;
+                if !order.contains(next) {
+                    order.push(*next);
+                }
+                continue;
+            }
+            ParsedLine::Directive(Directive::Start(label)) => {
+                if let Some((_, first)) = start {
+                    return Err(error(
+                        number,
+                        format!("START already given on line {}", first),
+                    ));
+                }
+                start = Some((label, number));
+                continue;
+            }
+            ParsedLine::Directive(Directive::Org(address)) => Item::Org(*address),
+            ParsedLine::Label(name) => Item::Label(name),
+            ParsedLine::Instruction(_) => {
+                if section == Section::Data {
+                    return Err(error(number, "instructions belong in the .text section"));
+                }
+                instructions += 1;
+                Item::Instruction(instructions - 1)
+            }
+            ParsedLine::Variable(name) => {
+                data.push((Item::Variable(name), number));
+                continue;
+            }
+            ParsedLine::Pseudo(_) => continue,
+        };
+
+        if !order.contains(&section) {
+            order.push(section);
+        }
+        match section {
+            Section::Text => text.push((item, number)),
+            Section::Data => data.push((item, number)),
+        }
+    }
+    for section in [Section::Text, Section::Data] {
+        if !order.contains(&section) {
+            order.push(section);
+        }
+    }
+
+    let mut layout = Layout {
+        addresses: vec![0; instructions],
+        ..Default::default()
+    };
+    let mut placement = Placement {
+        owners: HashMap::new(),
+    };
+    let mut location = 0;
+
+    for section in order {
+        let items = match section {
+            Section::Text => &text,
+            Section::Data => &data,
+        };
+        let data_base = location;
+
+        for (item, number) in items {
+            match item {
+                Item::Org(address) => location = *address,
+                Item::Label(name) => {
+                    if layout.labels.contains_key(*name) {
+                        return Err(SimpletronError::DuplicateLabel(name.to_string()));
+                    }
+                    layout.labels.insert(name.to_string(), location);
+                }
+                Item::Instruction(index) => {
+                    placement.claim(location, format!("the instruction on line {}", number))?;
+                    layout.addresses[*index] = location;
+                    location += 1;
+                }
+                Item::Variable(name) => {
+                    if layout.vars.contains_key(*name) {
+                        return Err(SimpletronError::DuplicateVariable(name.to_string()));
+                    }
+                    // Packed variables share cells, so only the first
+                    // occupant claims one.
+                    let address = match packing.and_then(|packing| packing.slots.get(*name)) {
+                        Some(slot) => data_base + slot,
+                        None => location,
+                    };
+                    if !placement.owners.contains_key(&address) || packing.is_none() {
+                        placement.claim(address, format!("variable `{}`", name))?;
+                    }
+                    layout.vars.insert(name.to_string(), address);
+                    location = location.max(address + 1);
+                }
+            }
+        }
+    }
+
+    layout.entry = match start {
+        Some((label, _)) => *layout
+            .labels
+            .get(label)
+            .ok_or_else(|| SimpletronError::UnknownLabel(label.to_string()))?,
+        None => layout.addresses.first().copied().unwrap_or(0),
+    };
+
+    Ok(layout)
+}
@@ -0,0 +1,465 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use crate::{
+    assembler::{
+        analysis::{CodeView, VarAccess, is_extension, liveness, var_access},
+        instruction::{AsmInstruction, Mnemonic, Operand},
+        layout::Directive,
+        parser::mnemonic_parser::ParsedLine,
+    },
+    vm::error::SimpletronError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    ReadBeforeWrite,
+    DeadStore,
+    UnusedVariable,
+    UnusedLabel,
+    UnreachableCode,
+    DivisionByZero,
+    FallThrough,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Lint::ReadBeforeWrite,
+        Lint::DeadStore,
+        Lint::UnusedVariable,
+        Lint::UnusedLabel,
+        Lint::UnreachableCode,
+        Lint::DivisionByZero,
+        Lint::FallThrough,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::ReadBeforeWrite => "read-before-write",
+            Lint::DeadStore => "dead-store",
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedLabel => "unused-label",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::DivisionByZero => "division-by-zero",
+            Lint::FallThrough => "fall-through",
+        }
+    }
+}
+
+impl FromStr for Lint {
+    type Err = SimpletronError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .into_iter()
+            .find(|lint| lint.name() == name)
+            .ok_or_else(|| SimpletronError::UnknownLint(name.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Per-lint levels. Lints without an explicit level warn.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<Lint, LintLevel>,
+}
+
+impl LintConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, lint: Lint, level: LintLevel) {
+        self.levels.insert(lint, level);
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        self.levels.get(&lint).copied().unwrap_or(LintLevel::Warn)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LintWarning {
+    pub lint: Lint,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: {} [{}]",
+            self.line,
+            self.message,
+            self.lint.name()
+        )
+    }
+}
+
+/// Runs every lint over a parsed program. Filtering by level is left to
+/// the caller.
+pub fn lint(parsed: &[ParsedLine], line_numbers: &[usize]) -> Vec<LintWarning> {
+    let code = CodeView::new(parsed, line_numbers);
+    let reachable = code.reachable();
+    let mut warnings = Vec::new();
+
+    unused_symbols(parsed, line_numbers, &code, &mut warnings);
+    // The flow-based lints would have to guess what an extension does.
+    if !code
+        .instructions
+        .iter()
+        .any(|instr| is_extension(instr.mnemonic))
+    {
+        unreachable_code(&code, &reachable, &mut warnings);
+        fall_through(&code, &reachable, &mut warnings);
+        read_before_write(parsed, &code, &reachable, &mut warnings);
+        dead_stores(&code, &reachable, &mut warnings);
+        division_by_zero(parsed, &code, &reachable, &mut warnings);
+    }
+
+    warnings.sort_by_key(|warning| warning.line);
+    warnings
+}
+
+fn declared_vars(parsed: &[ParsedLine]) -> Vec<&str> {
+    parsed
+        .iter()
+        .filter_map(|line| match line {
+            ParsedLine::Variable(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn unused_symbols(
+    parsed: &[ParsedLine],
+    line_numbers: &[usize],
+    code: &CodeView,
+    warnings: &mut Vec<LintWarning>,
+) {
+    let mut used_vars = HashSet::new();
+    let mut used_labels = HashSet::new();
+
+    for instr in &code.instructions {
+        match &instr.operand {
+            Some(Operand::Variable(name)) => {
+                used_vars.insert(name.as_str());
+            }
+            Some(Operand::Label(name)) => {
+                used_labels.insert(name.as_str());
+            }
+            _ => {}
+        }
+    }
+    for line in parsed {
+        if let ParsedLine::Directive(Directive::Start(name)) = line {
+            used_labels.insert(name.as_str());
+        }
+    }
+
+    for (line, number) in parsed.iter().zip(line_numbers) {
+        match line {
+            ParsedLine::Variable(name) if !used_vars.contains(name.as_str()) => {
+                warnings.push(LintWarning {
+                    lint: Lint::UnusedVariable,
+                    line: *number,
+                    message: format!("variable `{}` is never used", name),
+                });
+            }
+            ParsedLine::Label(name) if !used_labels.contains(name.as_str()) => {
+                warnings.push(LintWarning {
+                    lint: Lint::UnusedLabel,
+                    line: *number,
+                    message: format!("label `{}` is never jumped to", name),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn unreachable_code(code: &CodeView, reachable: &[bool], warnings: &mut Vec<LintWarning>) {
+    for index in 0..code.len() {
+        let starts_run = !reachable[index] && (index == 0 || reachable[index - 1]);
+        if !starts_run {
+            continue;
+        }
+
+        let message = match index
+            .checked_sub(1)
+            .map(|prev| code.instructions[prev].mnemonic)
+        {
+            Some(Mnemonic::Jump) => "unreachable code after JMP".to_string(),
+            Some(Mnemonic::Halt) => "unreachable code after HALT".to_string(),
+            _ => "unreachable code".to_string(),
+        };
+
+        warnings.push(LintWarning {
+            lint: Lint::UnreachableCode,
+            line: code.lines[index],
+            message,
+        });
+    }
+}
+
+fn fall_through(code: &CodeView, reachable: &[bool], warnings: &mut Vec<LintWarning>) {
+    for (index, &live) in reachable.iter().enumerate() {
+        if live && code.successors(index).contains(&code.len()) {
+            warnings.push(LintWarning {
+                lint: Lint::FallThrough,
+                line: code.lines[index],
+                message: "control can run past the last instruction into the variable cells"
+                    .to_string(),
+            });
+        }
+    }
+}
+
+/// Forward must-analysis: a read warns unless the variable is written on
+/// every path from the entry point.
+fn read_before_write(
+    parsed: &[ParsedLine],
+    code: &CodeView,
+    reachable: &[bool],
+    warnings: &mut Vec<LintWarning>,
+) {
+    if code.is_empty() {
+        return;
+    }
+
+    let all: HashSet<&str> = declared_vars(parsed).into_iter().collect();
+    let preds = code.predecessors();
+
+    let mut written_in: Vec<HashSet<&str>> = vec![all.clone(); code.len()];
+    written_in[code.entry] = HashSet::new();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in 0..code.len() {
+            let mut state = if index == code.entry {
+                HashSet::new()
+            } else {
+                all.clone()
+            };
+            for &pred in &preds[index] {
+                let mut out = written_in[pred].clone();
+                if let Some(VarAccess::Write(name)) = var_access(code.instructions[pred]) {
+                    out.insert(name);
+                }
+                state = state.intersection(&out).copied().collect();
+            }
+
+            if state != written_in[index] {
+                written_in[index] = state;
+                changed = true;
+            }
+        }
+    }
+
+    let mut reported = HashSet::new();
+    for index in 0..code.len() {
+        if !reachable[index] {
+            continue;
+        }
+        if let Some(VarAccess::Read(name)) = var_access(code.instructions[index])
+            && !written_in[index].contains(name)
+            && reported.insert(name)
+        {
+            warnings.push(LintWarning {
+                lint: Lint::ReadBeforeWrite,
+                line: code.lines[index],
+                message: format!("variable `{}` may be read before it is written", name),
+            });
+        }
+    }
+}
+
+/// Backward liveness: a store warns when no later path reads the value.
+fn dead_stores(code: &CodeView, reachable: &[bool], warnings: &mut Vec<LintWarning>) {
+    let live_out = liveness(code).live_out;
+
+    for index in 0..code.len() {
+        if !reachable[index] {
+            continue;
+        }
+        if let Some(VarAccess::Write(name)) = var_access(code.instructions[index])
+            && !live_out[index].contains(name)
+        {
+            warnings.push(LintWarning {
+                lint: Lint::DeadStore,
+                line: code.lines[index],
+                message: format!("value written to `{}` is never read", name),
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ConstState<'a> {
+    acc: Option<i64>,
+    vars: HashMap<&'a str, Option<i64>>,
+}
+
+impl<'a> ConstState<'a> {
+    fn join(&mut self, other: &ConstState<'a>) {
+        if self.acc != other.acc {
+            self.acc = None;
+        }
+        for (name, value) in self.vars.iter_mut() {
+            if other.vars.get(name) != Some(value) {
+                *value = None;
+            }
+        }
+    }
+
+    fn var(&self, name: &str) -> Option<i64> {
+        self.vars.get(name).copied().flatten()
+    }
+}
+
+fn fold(mnemonic: Mnemonic, acc: Option<i64>, value: Option<i64>) -> Option<i64> {
+    let (acc, value) = (acc?, value?);
+    match mnemonic {
+        Mnemonic::AddM | Mnemonic::AddI => acc.checked_add(value),
+        Mnemonic::SubM | Mnemonic::SubI => acc.checked_sub(value),
+        Mnemonic::MulM | Mnemonic::MulI => acc.checked_mul(value),
+        Mnemonic::DivM | Mnemonic::DivI => acc.checked_div(value),
+        Mnemonic::ModM | Mnemonic::ModI => acc.checked_rem(value),
+        _ => None,
+    }
+}
+
+fn transfer<'a>(state: &mut ConstState<'a>, instr: &'a AsmInstruction) {
+    let immediate = match &instr.operand {
+        Some(Operand::Immediate(value)) => Some(*value as i64),
+        _ => None,
+    };
+
+    match (instr.mnemonic, var_access(instr)) {
+        (Mnemonic::ReadI, _) => state.acc = None,
+        (Mnemonic::Read, Some(VarAccess::Write(name))) => {
+            state.vars.insert(name, None);
+        }
+        (Mnemonic::Store, Some(VarAccess::Write(name))) => {
+            state.vars.insert(name, state.acc);
+        }
+        (Mnemonic::Read | Mnemonic::Store, _) => {
+            // Raw address: any variable may have been overwritten.
+            state.vars.values_mut().for_each(|value| *value = None);
+        }
+        (Mnemonic::LoadM, Some(VarAccess::Read(name))) => state.acc = state.var(name),
+        (Mnemonic::LoadM, _) => state.acc = None,
+        (Mnemonic::LoadI, _) => state.acc = immediate,
+        (
+            Mnemonic::AddM | Mnemonic::SubM | Mnemonic::MulM | Mnemonic::DivM | Mnemonic::ModM,
+            access,
+        ) => {
+            let value = match access {
+                Some(VarAccess::Read(name)) => state.var(name),
+                _ => None,
+            };
+            state.acc = fold(instr.mnemonic, state.acc, value);
+        }
+        (Mnemonic::AddI | Mnemonic::SubI | Mnemonic::MulI | Mnemonic::DivI | Mnemonic::ModI, _) => {
+            state.acc = fold(instr.mnemonic, state.acc, immediate)
+        }
+        _ => {}
+    }
+}
+
+/// Constant propagation seeded with zeroed memory; flags divisors that are
+/// zero on every path. Only variables are tracked, so `DIVM 0` divides by
+/// whatever is in cell 0 and is left alone.
+fn division_by_zero(
+    parsed: &[ParsedLine],
+    code: &CodeView,
+    reachable: &[bool],
+    warnings: &mut Vec<LintWarning>,
+) {
+    if code.is_empty() {
+        return;
+    }
+
+    let entry = ConstState {
+        acc: Some(0),
+        vars: declared_vars(parsed)
+            .into_iter()
+            .map(|name| (name, Some(0)))
+            .collect(),
+    };
+
+    let mut states: Vec<Option<ConstState>> = vec![None; code.len() + 1];
+    states[code.entry] = Some(entry);
+    let mut worklist = vec![code.entry];
+
+    while let Some(index) = worklist.pop() {
+        if index >= code.len() {
+            continue;
+        }
+        let Some(mut state) = states[index].clone() else {
+            continue;
+        };
+        transfer(&mut state, code.instructions[index]);
+
+        for succ in code.successors(index) {
+            let merged = match &states[succ] {
+                Some(existing) => {
+                    let mut merged = existing.clone();
+                    merged.join(&state);
+                    merged
+                }
+                None => state.clone(),
+            };
+            if states[succ].as_ref() != Some(&merged) {
+                states[succ] = Some(merged);
+                worklist.push(succ);
+            }
+        }
+    }
+
+    for index in 0..code.len() {
+        let instr = code.instructions[index];
+        if !reachable[index] || !is_division(instr.mnemonic) {
+            continue;
+        }
+        let Some(state) = &states[index] else {
+            continue;
+        };
+
+        let message = match (&instr.operand, var_access(instr)) {
+            (_, Some(VarAccess::Read(name))) if state.var(name) == Some(0) => {
+                format!("`{}` is always zero here; this divides by zero", name)
+            }
+            (Some(Operand::Immediate(0)), _)
+                if matches!(instr.mnemonic, Mnemonic::DivI | Mnemonic::ModI) =>
+            {
+                "division by the immediate 0".to_string()
+            }
+            _ => continue,
+        };
+
+        warnings.push(LintWarning {
+            lint: Lint::DivisionByZero,
+            line: code.lines[index],
+            message,
+        });
+    }
+}
+
+fn is_division(mnemonic: Mnemonic) -> bool {
+    matches!(
+        mnemonic,
+        Mnemonic::DivM | Mnemonic::ModM | Mnemonic::DivI | Mnemonic::ModI
+    )
+}
@@ -0,0 +1,83 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::assembler::AssembledProgram;
+
+/// Width of the source column when a pseudo-op expansion follows it.
+const SOURCE_WIDTH: usize = 24;
+
+/// Renders an address-by-address listing of an assembled program, followed
+/// by its labels and variable cells. Words generated by a pseudo-op show
+/// the instruction they expand to; the source text is printed once. Empty
+/// cells left between `ORG` placements are skipped.
+pub fn render(program: &AssembledProgram) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "ADDR  WORD   LINE  SOURCE").unwrap();
+    let mut previous_line = None;
+    for (address, word) in program.words.iter().enumerate() {
+        let source = program.debug_info.lookup(address);
+        match (source, program.expansions.get(&address)) {
+            (Some(source), Some(expanded)) => {
+                let text = if previous_line == Some(source.number) {
+                    ""
+                } else {
+                    source.text.as_str()
+                };
+                writeln!(
+                    out,
+                    "{:0>2}    +{:0>4}  {:>4}  {:<width$}  => {}",
+                    address,
+                    word,
+                    source.number,
+                    text,
+                    expanded,
+                    width = SOURCE_WIDTH
+                )
+            }
+            (Some(source), None) => writeln!(
+                out,
+                "{:0>2}    +{:0>4}  {:>4}  {}",
+                address, word, source.number, source.text
+            ),
+            (None, _) if *word == 0 => continue,
+            (None, _) => writeln!(out, "{:0>2}    +{:0>4}", address, word),
+        }
+        .unwrap();
+        previous_line = source.map(|source| source.number);
+    }
+
+    let labels: BTreeMap<usize, Vec<&str>> =
+        program
+            .labels
+            .iter()
+            .fold(BTreeMap::new(), |mut acc, (name, address)| {
+                acc.entry(*address).or_insert_with(Vec::new).push(name);
+                acc
+            });
+    if !labels.is_empty() {
+        writeln!(out, "\nLABELS").unwrap();
+        for (address, mut names) in labels {
+            names.sort();
+            writeln!(out, "{:0>2}    {}", address, names.join(", ")).unwrap();
+        }
+    }
+
+    let cells: BTreeMap<usize, Vec<&str>> =
+        program
+            .vars
+            .iter()
+            .fold(BTreeMap::new(), |mut acc, (name, address)| {
+                acc.entry(*address).or_insert_with(Vec::new).push(name);
+                acc
+            });
+    if !cells.is_empty() {
+        writeln!(out, "\nVARIABLES").unwrap();
+        for (address, mut names) in cells {
+            names.sort();
+            let merged = if names.len() > 1 { "  (merged)" } else { "" };
+            writeln!(out, "{:0>2}    {}{}", address, names.join(", "), merged).unwrap();
+        }
+    }
+
+    out
+}
@@ -1,29 +1,102 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     assembler::{
         instruction::{Mnemonic, Operand},
+        lint::{LintConfig, LintLevel},
         parser::mnemonic_parser::{MnemonicParser, ParsedLine},
     },
-    vm::error::SimpletronError,
+    vm::{
+        debug_info::DebugInfo,
+        error::SimpletronError,
+        isa::{InstructionSet, Simpletron},
+    },
 };
 
+pub mod allocator;
+pub mod analysis;
+pub mod builder;
 pub mod encoder;
+pub mod generated;
 pub mod instruction;
+pub mod layout;
+pub mod lint;
+pub mod listing;
+pub mod optimizer;
 pub mod parser;
+pub mod program;
+pub mod pseudo;
+pub mod superopt;
+pub mod symbols;
+
+pub use builder::ProgramBuilder;
+pub use program::AssembledProgram;
+
+pub type LabelTable = HashMap<String, usize>;
+pub type VarTable = HashMap<String, usize>;
+
+#[derive(Debug, Clone)]
+pub struct AssemblerOptions {
+    pub lints: LintConfig,
+    pub optimize: bool,
+    /// Input sets used to check the optimized program against the original.
+    pub verify_inputs: Vec<Vec<i32>>,
+    pub pack_variables: bool,
+    /// Variables that packing leaves in a cell of their own, such as ones
+    /// read or written from outside the program.
+    pub separate_variables: Vec<String>,
+    /// Mnemonics accepted and opcode numbers emitted.
+    pub instruction_set: &'static dyn InstructionSet,
+}
 
-type LabelTable = HashMap<String, usize>;
-type VarTable = HashMap<String, usize>;
+impl Default for AssemblerOptions {
+    fn default() -> Self {
+        Self {
+            lints: LintConfig::default(),
+            optimize: false,
+            verify_inputs: Vec::new(),
+            pack_variables: false,
+            separate_variables: Vec::new(),
+            instruction_set: &Simpletron,
+        }
+    }
+}
 
 pub fn assemble(source: &str) -> Result<Vec<u16>, SimpletronError> {
-    let mut parsed = Vec::new();
+    Ok(assemble_program(source)?.words)
+}
+
+pub fn assemble_program(source: &str) -> Result<AssembledProgram, SimpletronError> {
+    assemble_with(source, &AssemblerOptions::default())
+}
 
-    for line in source.lines() {
-        if let Some(p) = MnemonicParser::parse_line(line)? {
+pub fn assemble_with(
+    source: &str,
+    options: &AssemblerOptions,
+) -> Result<AssembledProgram, SimpletronError> {
+    let mut parsed = Vec::new();
+    let mut line_numbers = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        if let Some(p) = MnemonicParser::parse_line_with(options.instruction_set, line)? {
             parsed.push(p);
+            line_numbers.push(index + 1);
         }
     }
 
+    let source_lines: Vec<&str> = source.lines().collect();
+    assemble_parsed(parsed, line_numbers, &source_lines, options)
+}
+
+/// Everything after parsing: pseudo-op expansion, lints, layout, encoding
+/// and the optional optimizer and packing passes. `line_numbers` index
+/// into `source_lines`, which supply the debug info text.
+fn assemble_parsed(
+    mut parsed: Vec<ParsedLine>,
+    mut line_numbers: Vec<usize>,
+    source_lines: &[&str],
+    options: &AssemblerOptions,
+) -> Result<AssembledProgram, SimpletronError> {
+    let isa = options.instruction_set;
     let has_halt = parsed.iter().any(|line| {
         if let ParsedLine::Instruction(instr) = line {
             instr.mnemonic == Mnemonic::Halt
@@ -36,47 +109,118 @@ pub fn assemble(source: &str) -> Result<Vec<u16>, SimpletronError> {
         return Err(SimpletronError::MissingHalt);
     }
 
-    let (labels, vars, _) = first_pass(&parsed)?;
-    second_pass(&parsed, &labels, &vars)
-}
+    let expansion = pseudo::expand(&mut parsed, &mut line_numbers, isa)?;
+    resolve_code_operands(&mut parsed);
 
-fn first_pass(lines: &[ParsedLine]) -> Result<(LabelTable, VarTable, usize), SimpletronError> {
-    let mut labels = HashMap::new();
-    let mut vars = HashMap::new();
+    let warnings: Vec<_> = lint::lint(&parsed, &line_numbers)
+        .into_iter()
+        .filter(|warning| options.lints.level(warning.lint) != LintLevel::Allow)
+        .collect();
 
-    let mut pc = 0;
+    if warnings
+        .iter()
+        .any(|warning| options.lints.level(warning.lint) == LintLevel::Deny)
+    {
+        return Err(SimpletronError::DeniedLints(warnings));
+    }
 
-    // First: count instructions & labels
-    for line in lines {
-        match line {
-            ParsedLine::Label(name) => {
-                if labels.contains_key(name) {
-                    return Err(SimpletronError::DuplicateLabel(name.clone()));
-                }
-                labels.insert(name.clone(), pc);
-            }
-            ParsedLine::Instruction(_) => pc += 1,
-            ParsedLine::Variable(_) => {}
-        }
+    let mut layout = layout::layout(&parsed, &line_numbers, None)?;
+    let mut words = layout.image(&second_pass(isa, &parsed, &layout.labels, &layout.vars)?);
+
+    let mut optimization = None;
+    if options.optimize {
+        let report = optimizer::optimize(&mut parsed, &mut line_numbers);
+        let opt_layout = layout::layout(&parsed, &line_numbers, None)?;
+        let opt_words = opt_layout.image(&second_pass(
+            isa,
+            &parsed,
+            &opt_layout.labels,
+            &opt_layout.vars,
+        )?);
+
+        optimizer::verify_equivalence(
+            (&words, &layout),
+            (&opt_words, &opt_layout),
+            &options.verify_inputs,
+            isa,
+        )?;
+
+        (words, layout) = (opt_words, opt_layout);
+        optimization = Some(report);
     }
 
-    let mut data_addr = pc;
+    if options.pack_variables
+        && let Some(packing) =
+            allocator::pack_variables(&parsed, &line_numbers, &options.separate_variables)
+    {
+        layout = layout::layout(&parsed, &line_numbers, Some(&packing))?;
+        words = layout.image(&second_pass(isa, &parsed, &layout.labels, &layout.vars)?);
+    }
 
-    // Second: allocate variables
-    for line in lines {
-        if let ParsedLine::Variable(name) = line {
-            if vars.contains_key(name) {
-                return Err(SimpletronError::DuplicateVariable(name.clone()));
-            }
-            vars.insert(name.clone(), data_addr);
-            data_addr += 1;
+    let mut debug_info = DebugInfo::new();
+    let mut expansions = HashMap::new();
+    let instructions = parsed
+        .iter()
+        .zip(&line_numbers)
+        .filter_map(|(line, number)| match line {
+            ParsedLine::Instruction(instr) => Some((instr, number)),
+            _ => None,
+        });
+    for ((instr, number), &address) in instructions.zip(&layout.addresses) {
+        let text = source_lines[number - 1].split(';').next().unwrap();
+        debug_info.insert(address, *number, text);
+        if expansion.pseudo_lines.contains(number) {
+            expansions.insert(address, instr.to_string());
         }
     }
 
-    Ok((labels, vars, pc))
+    let procedures = expansion.procedures(&layout.labels);
+
+    Ok(AssembledProgram {
+        words,
+        procedures,
+        labels: layout.labels,
+        vars: layout.vars,
+        entry: layout.entry,
+        debug_info,
+        expansions,
+        warnings,
+        optimization,
+    })
+}
+
+/// A memory operand that names a label rather than a variable refers to
+/// the code cell at that label, which lets programs patch their own
+/// instructions (e.g. `STORE next` followed by `next: LOADM 0`).
+fn resolve_code_operands(parsed: &mut [ParsedLine]) {
+    let labels: HashSet<String> = parsed
+        .iter()
+        .filter_map(|line| match line {
+            ParsedLine::Label(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    let vars: HashSet<String> = parsed
+        .iter()
+        .filter_map(|line| match line {
+            ParsedLine::Variable(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+
+    for line in parsed {
+        if let ParsedLine::Instruction(instr) = line
+            && let Some(Operand::Variable(name)) = &instr.operand
+            && labels.contains(name)
+            && !vars.contains(name)
+        {
+            instr.operand = Some(Operand::Label(name.clone()));
+        }
+    }
 }
 
 fn second_pass(
+    isa: &dyn InstructionSet,
     lines: &[ParsedLine],
     labels: &LabelTable,
     vars: &VarTable,
@@ -101,7 +245,7 @@ fn second_pass(
                 _ => {}
             }
 
-            output.push(encoder::encode(&instr)?);
+            output.push(encoder::encode_with(isa, &instr)?);
         }
     }
 
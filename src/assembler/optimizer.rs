@@ -0,0 +1,444 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    assembler::{
+        analysis::{CodeView, is_jump, is_memory_op},
+        instruction::{AsmInstruction, Mnemonic, Operand},
+        layout::{Directive, Layout},
+        parser::mnemonic_parser::ParsedLine,
+    },
+    vm::{
+        error::SimpletronError,
+        isa::InstructionSet,
+        machine::{Machine, StepOutcome},
+        memory::SimpleMemory,
+        processor::SimpleProcessor,
+    },
+};
+
+/// Largest value an immediate operand can hold.
+const MAX_IMMEDIATE: i64 = 99;
+
+#[derive(Debug, Clone)]
+pub struct Rewrite {
+    pub line: usize,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OptimizationReport {
+    pub words_before: usize,
+    pub words_after: usize,
+    pub rewrites: Vec<Rewrite>,
+    pub skipped: Option<String>,
+}
+
+impl OptimizationReport {
+    pub fn saved(&self) -> usize {
+        self.words_before - self.words_after
+    }
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(reason) = &self.skipped {
+            return write!(f, "optimizer skipped: {}", reason);
+        }
+
+        write!(
+            f,
+            "optimizer: {} -> {} words (saved {})",
+            self.words_before,
+            self.words_after,
+            self.saved()
+        )?;
+        for rewrite in &self.rewrites {
+            write!(f, "\n  line {}: {}", rewrite.line, rewrite.description)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rewrites a parsed program in place until no rule applies.
+///
+/// Programs that use numeric addresses or modify their own code are left
+/// untouched, since removing instructions would move the cells those
+/// addresses refer to. So are programs with extension instructions, whose
+/// effects the rules cannot see.
+pub fn optimize(parsed: &mut Vec<ParsedLine>, line_numbers: &mut Vec<usize>) -> OptimizationReport {
+    let mut lines: Vec<(usize, ParsedLine)> =
+        line_numbers.drain(..).zip(parsed.drain(..)).collect();
+
+    let mut report = OptimizationReport {
+        words_before: count_instructions(&lines),
+        ..Default::default()
+    };
+
+    if let Some(reason) = position_dependent(&lines) {
+        report.skipped = Some(reason);
+    } else {
+        loop {
+            let before = report.rewrites.len();
+
+            remove_reload_after_store(&mut lines, &mut report.rewrites);
+            fold_immediates(&mut lines, &mut report.rewrites);
+            remove_noop_arithmetic(&mut lines, &mut report.rewrites);
+            thread_jumps(&mut lines, &mut report.rewrites);
+            remove_jumps_to_next(&mut lines, &mut report.rewrites);
+            remove_unreachable(&mut lines, &mut report.rewrites);
+
+            if report.rewrites.len() == before {
+                break;
+            }
+        }
+    }
+
+    report.words_after = count_instructions(&lines);
+    for (number, line) in lines {
+        line_numbers.push(number);
+        parsed.push(line);
+    }
+    report
+}
+
+fn count_instructions(lines: &[(usize, ParsedLine)]) -> usize {
+    lines
+        .iter()
+        .filter(|(_, line)| matches!(line, ParsedLine::Instruction(_)))
+        .count()
+}
+
+fn position_dependent(lines: &[(usize, ParsedLine)]) -> Option<String> {
+    lines.iter().find_map(|(number, line)| match line {
+        ParsedLine::Instruction(AsmInstruction {
+            mnemonic: Mnemonic::Extension(name),
+            ..
+        }) => Some(format!(
+            "line {} uses the extension instruction {}",
+            number, name
+        )),
+        ParsedLine::Instruction(AsmInstruction {
+            mnemonic,
+            operand: Some(Operand::Immediate(_)),
+        }) if is_jump(*mnemonic) || is_memory_op(*mnemonic) => {
+            Some(format!("line {} uses a numeric address", number))
+        }
+        ParsedLine::Instruction(AsmInstruction {
+            mnemonic,
+            operand: Some(Operand::Label(_)),
+        }) if is_memory_op(*mnemonic) => Some(format!("line {} reads or writes code", number)),
+        ParsedLine::Directive(Directive::Org(_) | Directive::Section(_)) => {
+            Some(format!("line {} sets the memory layout", number))
+        }
+        _ => None,
+    })
+}
+
+fn instruction(line: &(usize, ParsedLine)) -> Option<&AsmInstruction> {
+    match &line.1 {
+        ParsedLine::Instruction(instr) => Some(instr),
+        _ => None,
+    }
+}
+
+fn immediate(instr: &AsmInstruction) -> Option<i64> {
+    match instr.operand {
+        Some(Operand::Immediate(value)) => Some(value as i64),
+        _ => None,
+    }
+}
+
+/// `STORE x` leaves x in the accumulator, so a directly following
+/// `LOADM x` is redundant.
+fn remove_reload_after_store(lines: &mut Vec<(usize, ParsedLine)>, rewrites: &mut Vec<Rewrite>) {
+    let mut index = 0;
+    while index + 1 < lines.len() {
+        let redundant = match (instruction(&lines[index]), instruction(&lines[index + 1])) {
+            (Some(store), Some(load)) => {
+                store.mnemonic == Mnemonic::Store
+                    && load.mnemonic == Mnemonic::LoadM
+                    && matches!(
+                        (&store.operand, &load.operand),
+                        (Some(Operand::Variable(a)), Some(Operand::Variable(b))) if a == b
+                    )
+            }
+            _ => false,
+        };
+
+        if redundant {
+            let (number, _) = lines.remove(index + 1);
+            rewrites.push(Rewrite {
+                line: number,
+                description: "removed LOADM of the value just stored".to_string(),
+            });
+        } else {
+            index += 1;
+        }
+    }
+}
+
+/// `LOADI a` followed by immediate arithmetic becomes a single `LOADI`
+/// when the result still fits in an operand.
+fn fold_immediates(lines: &mut Vec<(usize, ParsedLine)>, rewrites: &mut Vec<Rewrite>) {
+    let mut index = 0;
+    while index + 1 < lines.len() {
+        let folded = match (instruction(&lines[index]), instruction(&lines[index + 1])) {
+            (Some(load), Some(op)) if load.mnemonic == Mnemonic::LoadI => {
+                match (immediate(load), immediate(op)) {
+                    (Some(a), Some(b)) => fold(op.mnemonic, a, b),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        match folded {
+            Some(value) if (0..=MAX_IMMEDIATE).contains(&value) => {
+                let (number, _) = lines.remove(index + 1);
+                lines[index].1 = ParsedLine::Instruction(AsmInstruction {
+                    mnemonic: Mnemonic::LoadI,
+                    operand: Some(Operand::Immediate(value as usize)),
+                });
+                rewrites.push(Rewrite {
+                    line: number,
+                    description: format!("folded into LOADI {}", value),
+                });
+            }
+            _ => index += 1,
+        }
+    }
+}
+
+fn fold(mnemonic: Mnemonic, a: i64, b: i64) -> Option<i64> {
+    match mnemonic {
+        Mnemonic::AddI => Some(a + b),
+        Mnemonic::SubI => Some(a - b),
+        Mnemonic::MulI => Some(a * b),
+        Mnemonic::DivI => a.checked_div(b),
+        Mnemonic::ModI => a.checked_rem(b),
+        _ => None,
+    }
+}
+
+fn remove_noop_arithmetic(lines: &mut Vec<(usize, ParsedLine)>, rewrites: &mut Vec<Rewrite>) {
+    lines.retain(|line| {
+        let noop = instruction(line).is_some_and(|instr| {
+            matches!(
+                (instr.mnemonic, immediate(instr)),
+                (Mnemonic::AddI | Mnemonic::SubI, Some(0))
+                    | (Mnemonic::MulI | Mnemonic::DivI, Some(1))
+            )
+        });
+        if noop {
+            rewrites.push(Rewrite {
+                line: line.0,
+                description: "removed no-op arithmetic".to_string(),
+            });
+        }
+        !noop
+    });
+}
+
+/// Label of a `JMP` that is the first instruction after `label`, if any.
+fn jump_after_labels(lines: &[(usize, ParsedLine)]) -> HashMap<String, String> {
+    let mut pending = Vec::new();
+    let mut forwards = HashMap::new();
+
+    for (_, line) in lines {
+        match line {
+            ParsedLine::Label(name) => pending.push(name.clone()),
+            ParsedLine::Instruction(instr) => {
+                if let (Mnemonic::Jump, Some(Operand::Label(target))) =
+                    (instr.mnemonic, &instr.operand)
+                {
+                    for name in &pending {
+                        forwards.insert(name.clone(), target.clone());
+                    }
+                }
+                pending.clear();
+            }
+            ParsedLine::Variable(_) | ParsedLine::Pseudo(_) | ParsedLine::Directive(_) => {}
+        }
+    }
+
+    forwards
+}
+
+fn thread_jumps(lines: &mut [(usize, ParsedLine)], rewrites: &mut Vec<Rewrite>) {
+    let forwards = jump_after_labels(lines);
+
+    for (number, line) in lines.iter_mut() {
+        let ParsedLine::Instruction(AsmInstruction {
+            mnemonic,
+            operand: Some(Operand::Label(target)),
+        }) = line
+        else {
+            continue;
+        };
+        if !is_jump(*mnemonic) {
+            continue;
+        }
+
+        let mut seen = HashSet::from([target.clone()]);
+        let mut last = target.clone();
+        while let Some(next) = forwards.get(&last) {
+            if !seen.insert(next.clone()) {
+                break;
+            }
+            last = next.clone();
+        }
+
+        if last != *target {
+            rewrites.push(Rewrite {
+                line: *number,
+                description: format!("threaded jump to `{}` through to `{}`", target, last),
+            });
+            *target = last;
+        }
+    }
+}
+
+fn remove_jumps_to_next(lines: &mut Vec<(usize, ParsedLine)>, rewrites: &mut Vec<Rewrite>) {
+    let mut index = 0;
+    while index < lines.len() {
+        let target = match instruction(&lines[index]) {
+            Some(AsmInstruction {
+                mnemonic,
+                operand: Some(Operand::Label(target)),
+            }) if is_jump(*mnemonic) => Some(target.clone()),
+            _ => None,
+        };
+
+        let lands_on_next = target.is_some_and(|target| {
+            lines[index + 1..]
+                .iter()
+                .map_while(|(_, line)| match line {
+                    ParsedLine::Label(name) => Some(name),
+                    _ => None,
+                })
+                .any(|name| *name == target)
+        });
+
+        if lands_on_next {
+            let (number, _) = lines.remove(index);
+            rewrites.push(Rewrite {
+                line: number,
+                description: "removed jump to the next instruction".to_string(),
+            });
+        } else {
+            index += 1;
+        }
+    }
+}
+
+fn remove_unreachable(lines: &mut Vec<(usize, ParsedLine)>, rewrites: &mut Vec<Rewrite>) {
+    let (numbers, parsed): (Vec<usize>, Vec<ParsedLine>) = lines.iter().cloned().unzip();
+    let reachable = CodeView::new(&parsed, &numbers).reachable();
+
+    let mut pc = 0;
+    lines.retain(|(number, line)| {
+        if !matches!(line, ParsedLine::Instruction(_)) {
+            return true;
+        }
+        let keep = reachable[pc];
+        pc += 1;
+        if !keep {
+            rewrites.push(Rewrite {
+                line: *number,
+                description: "removed unreachable instruction".to_string(),
+            });
+        }
+        keep
+    });
+}
+
+/// What a program did when run on one set of inputs.
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    outputs: Vec<i32>,
+    vars: Vec<(String, i32)>,
+    end: String,
+}
+
+const SIMULATION_STEPS: usize = 100_000;
+
+/// Runs a word image with scripted input and records everything the
+/// program made observable.
+fn simulate(
+    isa: &'static dyn InstructionSet,
+    words: &[u16],
+    layout: &Layout,
+    inputs: &[i32],
+) -> Outcome {
+    let mut machine = Machine::new(SimpleProcessor::new(), SimpleMemory::new(None));
+    machine.set_instruction_set(isa);
+    if let Err(error) = machine
+        .load(words)
+        .and_then(|_| machine.set_pc(layout.entry))
+    {
+        return Outcome {
+            outputs: Vec::new(),
+            vars: Vec::new(),
+            end: error.to_string(),
+        };
+    }
+    for input in inputs {
+        machine.push_input(*input);
+    }
+
+    let end = match machine.run_for(SIMULATION_STEPS) {
+        StepOutcome::Continued => "step limit reached".to_string(),
+        StepOutcome::Halted => format!(
+            "halted with status {}",
+            machine.exit_status().unwrap_or_default()
+        ),
+        StepOutcome::AwaitingInput => "input exhausted".to_string(),
+        StepOutcome::Fault => machine
+            .fault()
+            .map_or_else(String::new, |fault| fault.error.to_string()),
+    };
+
+    let outputs = machine
+        .take_output()
+        .iter()
+        .map(|output| output.value())
+        .collect();
+    let mut vars: Vec<(String, i32)> = layout
+        .vars
+        .iter()
+        .map(|(name, address)| {
+            (
+                name.clone(),
+                machine.read_memory(*address).unwrap_or_default(),
+            )
+        })
+        .collect();
+    vars.sort();
+
+    Outcome { outputs, vars, end }
+}
+
+/// Runs both programs on every input set and fails on the first
+/// observable difference in outputs, variables or termination.
+pub fn verify_equivalence(
+    original: (&[u16], &Layout),
+    optimized: (&[u16], &Layout),
+    input_sets: &[Vec<i32>],
+    isa: &'static dyn InstructionSet,
+) -> Result<(), SimpletronError> {
+    for inputs in input_sets {
+        let expected = simulate(isa, original.0, original.1, inputs);
+        let actual = simulate(isa, optimized.0, optimized.1, inputs);
+
+        if expected != actual {
+            return Err(SimpletronError::OptimizationMismatch {
+                inputs: inputs.clone(),
+                detail: format!("expected {:?}, got {:?}", expected, actual),
+            });
+        }
+    }
+    Ok(())
+}
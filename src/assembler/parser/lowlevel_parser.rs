@@ -30,7 +30,7 @@ impl ParserInterface for LowLevelParser {
 
         Ok(Some(ParsedInstruction {
             address,
-            data: parts[1].to_string(),
+            data: parts[1].parse()?,
         }))
     }
 }
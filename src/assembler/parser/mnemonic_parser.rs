@@ -1,5 +1,8 @@
-use crate::assembler::instruction::{AsmInstruction, Mnemonic, Operand};
+use crate::assembler::instruction::{AsmInstruction, Operand};
+use crate::assembler::layout::Directive;
+use crate::assembler::pseudo::PseudoOp;
 use crate::vm::error::SimpletronError;
+use crate::vm::isa::{InstructionSet, OperandKind, Simpletron};
 
 pub struct MnemonicParser;
 
@@ -8,10 +11,23 @@ pub enum ParsedLine {
     Label(String),
     Variable(String),
     Instruction(AsmInstruction),
+    /// Expanded into instructions by [`crate::assembler::pseudo::expand`].
+    Pseudo(PseudoOp),
+    /// `ORG`, `.text`, `.data` or `START`, used by [`crate::assembler::layout`].
+    Directive(Directive),
 }
 
 impl MnemonicParser {
     pub fn parse_line(line: &str) -> Result<Option<ParsedLine>, SimpletronError> {
+        Self::parse_line_with(&Simpletron, line)
+    }
+
+    /// Parses a line whose instructions are written in the mnemonics of
+    /// `isa`.
+    pub fn parse_line_with(
+        isa: &dyn InstructionSet,
+        line: &str,
+    ) -> Result<Option<ParsedLine>, SimpletronError> {
         let code = line.split(';').next().unwrap().trim();
         if code.is_empty() {
             return Ok(None);
@@ -30,53 +46,38 @@ impl MnemonicParser {
             return Ok(Some(ParsedLine::Variable(parts[1].to_string())));
         }
 
-        // 3️⃣ REAL instruction parsing starts here
-        let mnemonic = match parts[0] {
-            "READ" => Mnemonic::Read,
-            "READI" => Mnemonic::ReadI,
-            "WRITE" => Mnemonic::Write,
-            "WRITEA" => Mnemonic::WriteAcc,
-
-            "LOADM" => Mnemonic::LoadM,
-            "LOADI" => Mnemonic::LoadI,
-            "STORE" => Mnemonic::Store,
-
-            "ADDM" => Mnemonic::AddM,
-            "ADDI" => Mnemonic::AddI,
-            "SUBM" => Mnemonic::SubM,
-            "SUBI" => Mnemonic::SubI,
-            "DIVM" => Mnemonic::DivM,
-            "DIVI" => Mnemonic::DivI,
-            "MODM" => Mnemonic::ModM,
-            "MODI" => Mnemonic::ModI,
-            "MULM" => Mnemonic::MulM,
-            "MULI" => Mnemonic::MulI,
-
-            "JMP" => Mnemonic::Jump,
-            "JZ" => Mnemonic::JumpIfZero,
-            "JN" => Mnemonic::JumpIfNegative,
-            "JNZ" => Mnemonic::JumpIfNotZero,
-            "JG" => Mnemonic::JumpIfGreaterThanZero,
+        if let Some(directive) = Directive::parse(&parts)? {
+            return Ok(Some(ParsedLine::Directive(directive)));
+        }
 
-            "HALT" => Mnemonic::Halt,
+        if let Some(op) = PseudoOp::parse(&parts)? {
+            return Ok(Some(ParsedLine::Pseudo(op)));
+        }
 
-            _ => return Err(SimpletronError::InvalidInstruction(parts[0].to_string())),
-        };
+        // 3️⃣ REAL instruction parsing starts here
+        let spec = isa
+            .by_mnemonic(parts[0])
+            .ok_or_else(|| SimpletronError::InvalidInstruction(parts[0].to_string()))?;
 
-        // operand handling (as you already fixed)
-        let operand = match mnemonic {
-            Mnemonic::Halt | Mnemonic::WriteAcc => {
+        let operand = match spec.operand {
+            OperandKind::None => {
                 if parts.len() != 1 {
                     return Err(SimpletronError::InvalidInstructionLine);
                 }
                 None
             }
 
-            Mnemonic::Jump
-            | Mnemonic::JumpIfZero
-            | Mnemonic::JumpIfNegative
-            | Mnemonic::JumpIfNotZero
-            | Mnemonic::JumpIfGreaterThanZero => {
+            // Optional exit status, 0 when omitted
+            OperandKind::Status => match parts[..] {
+                [_] => None,
+                [_, raw] => match raw.parse() {
+                    Ok(status) if status < 100 => Some(Operand::Immediate(status)),
+                    _ => return Err(SimpletronError::InvalidOperand(raw.to_string())),
+                },
+                _ => return Err(SimpletronError::InvalidInstructionLine),
+            },
+
+            OperandKind::Target => {
                 if parts.len() != 2 {
                     return Err(SimpletronError::InvalidInstructionLine);
                 }
@@ -88,7 +89,7 @@ impl MnemonicParser {
                 })
             }
 
-            _ => {
+            OperandKind::Address | OperandKind::Immediate => {
                 if parts.len() != 2 {
                     return Err(SimpletronError::InvalidInstructionLine);
                 }
@@ -102,7 +103,7 @@ impl MnemonicParser {
         };
 
         Ok(Some(ParsedLine::Instruction(AsmInstruction {
-            mnemonic,
+            mnemonic: spec.operation,
             operand,
         })))
     }
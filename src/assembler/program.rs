@@ -0,0 +1,26 @@
+use std::collections::HashMap;
+
+use crate::{
+    assembler::{
+        LabelTable, VarTable, lint::LintWarning, optimizer::OptimizationReport, pseudo::Procedure,
+    },
+    vm::debug_info::DebugInfo,
+};
+
+/// Output of the assembler: the word image plus the symbols and source
+/// mapping that produced it.
+#[derive(Debug, Clone)]
+pub struct AssembledProgram {
+    pub words: Vec<u16>,
+    pub labels: LabelTable,
+    pub vars: VarTable,
+    /// Address execution starts at.
+    pub entry: usize,
+    pub procedures: Vec<Procedure>,
+    pub debug_info: DebugInfo,
+    /// Generated instruction text for addresses that came from a
+    /// pseudo-op, e.g. `ADDI   1` for the second word of `INC x`.
+    pub expansions: HashMap<usize, String>,
+    pub warnings: Vec<LintWarning>,
+    pub optimization: Option<OptimizationReport>,
+}
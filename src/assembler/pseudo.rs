@@ -0,0 +1,608 @@
+use std::collections::HashSet;
+
+use crate::{
+    assembler::{
+        LabelTable,
+        instruction::{AsmInstruction, Mnemonic, Operand},
+        parser::mnemonic_parser::ParsedLine,
+    },
+    vm::{error::SimpletronError, isa::InstructionSet},
+};
+
+/// Assembler directives that expand into ordinary instructions before
+/// the first pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PseudoOp {
+    /// `PROC name`: starts a procedure.
+    Proc(String),
+    /// `ENDP`: ends the current procedure and emits its exit cell.
+    EndProc,
+    /// `CALL name`
+    Call(String),
+    /// `RET`
+    Ret,
+    /// `IF <condition>`: runs the block when the accumulator matches.
+    If(Condition),
+    Else,
+    EndIf,
+    /// `WHILE <condition>`: tests the accumulator before every iteration.
+    While(Condition),
+    EndWhile,
+    Repeat,
+    /// `UNTIL <condition>`: tests the accumulator after every iteration.
+    Until(Condition),
+    /// `INC x`
+    Inc(String),
+    /// `DEC x`
+    Dec(String),
+    /// `NEG`: negates the accumulator.
+    Neg,
+    /// `CLR x`
+    Clr(String),
+    /// `MOV dst, src`
+    Mov(String, String),
+    /// `SWAP a, b`
+    Swap(String, String),
+    /// `ABS`: replaces the accumulator with its absolute value.
+    Abs,
+}
+
+/// Assembler-managed scratch cell used by `NEG`, `ABS` and `SWAP`.
+pub const SCRATCH: &str = "__scratch";
+
+/// Accumulator test used by the block pseudo-ops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Zero,
+    NotZero,
+    Neg,
+    NotNeg,
+    Pos,
+    NotPos,
+}
+
+impl Condition {
+    fn parse(name: &str) -> Result<Condition, SimpletronError> {
+        match name {
+            "ZERO" => Ok(Condition::Zero),
+            "NOTZERO" => Ok(Condition::NotZero),
+            "NEG" => Ok(Condition::Neg),
+            "NOTNEG" => Ok(Condition::NotNeg),
+            "POS" => Ok(Condition::Pos),
+            "NOTPOS" => Ok(Condition::NotPos),
+            _ => Err(SimpletronError::InvalidOperand(name.to_string())),
+        }
+    }
+
+    /// Jumps that are taken exactly when the condition does not hold.
+    fn inverse_jumps(self) -> &'static [Mnemonic] {
+        match self {
+            Condition::Zero => &[Mnemonic::JumpIfNotZero],
+            Condition::NotZero => &[Mnemonic::JumpIfZero],
+            Condition::Neg => &[Mnemonic::JumpIfGreaterThanZero, Mnemonic::JumpIfZero],
+            Condition::NotNeg => &[Mnemonic::JumpIfNegative],
+            Condition::Pos => &[Mnemonic::JumpIfNegative, Mnemonic::JumpIfZero],
+            Condition::NotPos => &[Mnemonic::JumpIfGreaterThanZero],
+        }
+    }
+}
+
+impl PseudoOp {
+    /// Parses a pseudo-op, or returns `None` if `parts` is not one.
+    pub fn parse(parts: &[&str]) -> Result<Option<PseudoOp>, SimpletronError> {
+        let op = match parts[0] {
+            "PROC" => PseudoOp::Proc(single_operand(parts)?),
+            "CALL" => PseudoOp::Call(single_operand(parts)?),
+            "ENDP" => no_operand(parts, PseudoOp::EndProc)?,
+            "RET" => no_operand(parts, PseudoOp::Ret)?,
+            "IF" => PseudoOp::If(Condition::parse(&single_operand(parts)?)?),
+            "ELSE" => no_operand(parts, PseudoOp::Else)?,
+            "ENDIF" => no_operand(parts, PseudoOp::EndIf)?,
+            "WHILE" => PseudoOp::While(Condition::parse(&single_operand(parts)?)?),
+            "ENDW" => no_operand(parts, PseudoOp::EndWhile)?,
+            "REPEAT" => no_operand(parts, PseudoOp::Repeat)?,
+            "UNTIL" => PseudoOp::Until(Condition::parse(&single_operand(parts)?)?),
+            "INC" => PseudoOp::Inc(single_operand(parts)?),
+            "DEC" => PseudoOp::Dec(single_operand(parts)?),
+            "NEG" => no_operand(parts, PseudoOp::Neg)?,
+            "CLR" => PseudoOp::Clr(single_operand(parts)?),
+            "MOV" => {
+                let (dst, src) = two_operands(parts)?;
+                PseudoOp::Mov(dst, src)
+            }
+            "SWAP" => {
+                let (a, b) = two_operands(parts)?;
+                PseudoOp::Swap(a, b)
+            }
+            "ABS" => no_operand(parts, PseudoOp::Abs)?,
+            _ => return Ok(None),
+        };
+        Ok(Some(op))
+    }
+}
+
+fn single_operand(parts: &[&str]) -> Result<String, SimpletronError> {
+    match parts {
+        [_, operand] => Ok(operand.to_string()),
+        _ => Err(SimpletronError::InvalidInstructionLine),
+    }
+}
+
+/// Splits `OP a, b` (spaces around the comma are optional).
+fn two_operands(parts: &[&str]) -> Result<(String, String), SimpletronError> {
+    let operands = parts[1..].join(" ");
+    match operands.split(',').map(str::trim).collect::<Vec<_>>()[..] {
+        [first, second] if !first.is_empty() && !second.is_empty() => {
+            Ok((first.to_string(), second.to_string()))
+        }
+        _ => Err(SimpletronError::InvalidInstructionLine),
+    }
+}
+
+fn no_operand(parts: &[&str], op: PseudoOp) -> Result<PseudoOp, SimpletronError> {
+    match parts {
+        [_] => Ok(op),
+        _ => Err(SimpletronError::InvalidInstructionLine),
+    }
+}
+
+/// A `PROC ... ENDP` block. `start` is the entry point and `end` the exit
+/// cell the return jump is written into.
+#[derive(Debug, Clone)]
+pub struct Procedure {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Procedure names and exit labels collected during expansion (addresses
+/// are filled in once labels are laid out), and the source lines that held
+/// a pseudo-op.
+#[derive(Debug, Clone, Default)]
+pub struct Expansion {
+    procedures: Vec<(String, String)>,
+    pub pseudo_lines: HashSet<usize>,
+}
+
+impl Expansion {
+    pub fn procedures(&self, labels: &LabelTable) -> Vec<Procedure> {
+        self.procedures
+            .iter()
+            .filter_map(|(name, exit)| {
+                Some(Procedure {
+                    name: name.clone(),
+                    start: *labels.get(name)?,
+                    end: *labels.get(exit)?,
+                })
+            })
+            .collect()
+    }
+}
+
+fn exit_label(name: &str) -> String {
+    format!("__{}_exit", name)
+}
+
+/// Rejects calls that lead back to the calling procedure, which would
+/// overwrite its return address. Calls are added in source order, and the
+/// one that closes a cycle is reported.
+fn check_recursion(calls: &[(String, String, usize)]) -> Result<(), SimpletronError> {
+    for (index, (caller, callee, line)) in calls.iter().enumerate() {
+        if let Some(path) = call_path(&calls[..index], callee, caller) {
+            return Err(error(
+                *line,
+                format!(
+                    "PROC {} cannot call itself, but CALL {} leads back to it: {} -> {}",
+                    caller,
+                    callee,
+                    caller,
+                    path.join(" -> ")
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A chain of calls from `from` to `to`, both included.
+fn call_path<'a>(
+    calls: &'a [(String, String, usize)],
+    from: &'a str,
+    to: &str,
+) -> Option<Vec<&'a str>> {
+    let mut stack = vec![vec![from]];
+    let mut seen = HashSet::new();
+    while let Some(path) = stack.pop() {
+        let last = *path.last().unwrap();
+        if last == to {
+            return Some(path);
+        }
+        if !seen.insert(last) {
+            continue;
+        }
+        for (caller, callee, _) in calls {
+            if caller == last {
+                let mut next = path.clone();
+                next.push(callee.as_str());
+                stack.push(next);
+            }
+        }
+    }
+    None
+}
+
+fn error(line: usize, message: impl Into<String>) -> SimpletronError {
+    SimpletronError::InvalidPseudoOp {
+        line,
+        message: message.into(),
+    }
+}
+
+/// An open block, with the line that opened it.
+enum Block {
+    Proc {
+        name: String,
+        line: usize,
+    },
+    If {
+        skip: String,
+        has_else: bool,
+        line: usize,
+    },
+    While {
+        top: String,
+        end: String,
+        line: usize,
+    },
+    Repeat {
+        top: String,
+        line: usize,
+    },
+}
+
+impl Block {
+    fn opener(&self) -> &'static str {
+        match self {
+            Block::Proc { .. } => "PROC",
+            Block::If { .. } => "IF",
+            Block::While { .. } => "WHILE",
+            Block::Repeat { .. } => "REPEAT",
+        }
+    }
+
+    fn line(&self) -> usize {
+        match self {
+            Block::Proc { line, .. }
+            | Block::If { line, .. }
+            | Block::While { line, .. }
+            | Block::Repeat { line, .. } => *line,
+        }
+    }
+}
+
+struct Expander {
+    isa: &'static dyn InstructionSet,
+    lines: Vec<ParsedLine>,
+    line_numbers: Vec<usize>,
+    line: usize,
+    labels: usize,
+    blocks: Vec<Block>,
+    uses_scratch: bool,
+    /// `CALL`s made inside a `PROC`: caller, callee and source line.
+    calls: Vec<(String, String, usize)>,
+}
+
+impl Expander {
+    fn push(&mut self, line: ParsedLine) {
+        self.lines.push(line);
+        self.line_numbers.push(self.line);
+    }
+
+    fn emit(&mut self, mnemonic: Mnemonic, operand: Option<Operand>) {
+        self.push(ParsedLine::Instruction(AsmInstruction {
+            mnemonic,
+            operand,
+        }));
+    }
+
+    fn var(&mut self, mnemonic: Mnemonic, name: &str) {
+        self.emit(mnemonic, Some(Operand::Variable(name.to_string())));
+    }
+
+    fn immediate(&mut self, mnemonic: Mnemonic, value: usize) {
+        self.emit(mnemonic, Some(Operand::Immediate(value)));
+    }
+
+    /// acc = 0 - acc, through the scratch cell.
+    fn negate(&mut self) {
+        self.uses_scratch = true;
+        self.var(Mnemonic::Store, SCRATCH);
+        self.immediate(Mnemonic::LoadI, 0);
+        self.var(Mnemonic::SubM, SCRATCH);
+    }
+
+    fn jump(&mut self, mnemonic: Mnemonic, label: &str) {
+        self.emit(mnemonic, Some(Operand::Label(label.to_string())));
+    }
+
+    /// Jumps to `label` unless the accumulator matches `condition`.
+    fn jump_unless(&mut self, condition: Condition, label: &str) {
+        for &jump in condition.inverse_jumps() {
+            self.jump(jump, label);
+        }
+    }
+
+    fn new_label(&mut self, prefix: &str) -> String {
+        self.labels += 1;
+        format!("__{}{}", prefix, self.labels)
+    }
+
+    fn procedure(&self) -> Option<&str> {
+        self.blocks.iter().find_map(|block| match block {
+            Block::Proc { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Pops the innermost block, which `closer` must be able to close.
+    fn close(&mut self, closer: &str, opener: &str) -> Result<Block, SimpletronError> {
+        match self.blocks.pop() {
+            Some(block) if block.opener() == opener => Ok(block),
+            Some(block) => Err(error(
+                self.line,
+                format!(
+                    "{} does not match {} on line {}",
+                    closer,
+                    block.opener(),
+                    block.line()
+                ),
+            )),
+            None => Err(error(self.line, format!("{} without {}", closer, opener))),
+        }
+    }
+
+    fn expand(&mut self, op: &PseudoOp, procedures: &[&str]) -> Result<(), SimpletronError> {
+        let line = self.line;
+
+        match op {
+            PseudoOp::Proc(name) => {
+                if let Some(block) = self.blocks.last() {
+                    return Err(error(
+                        line,
+                        format!(
+                            "PROC {} inside {} on line {}",
+                            name,
+                            block.opener(),
+                            block.line()
+                        ),
+                    ));
+                }
+                self.blocks.push(Block::Proc {
+                    name: name.clone(),
+                    line,
+                });
+                self.push(ParsedLine::Label(name.clone()));
+            }
+            PseudoOp::EndProc => {
+                let Block::Proc { name, .. } = self.close("ENDP", "PROC")? else {
+                    unreachable!("close checks the block kind");
+                };
+                let exit = exit_label(&name);
+                self.push(ParsedLine::Label(exit));
+                // Overwritten with the return jump by every CALL.
+                self.emit(Mnemonic::Halt, None);
+            }
+            PseudoOp::Ret => {
+                let name = self
+                    .procedure()
+                    .ok_or_else(|| error(line, "RET outside a PROC"))?;
+                let exit = exit_label(name);
+                self.jump(Mnemonic::Jump, &exit);
+            }
+            PseudoOp::Call(target) => {
+                if !procedures.contains(&target.as_str()) {
+                    return Err(error(line, format!("`{}` is not a PROC", target)));
+                }
+                if let Some(caller) = self.procedure() {
+                    if caller == target {
+                        return Err(error(line, format!("PROC {} cannot call itself", target)));
+                    }
+                    self.calls.push((caller.to_string(), target.clone(), line));
+                }
+
+                // acc = the word `JMP <return>`: the jump's opcode times 100
+                // plus the return address.
+                let jump = self.isa.encode(Mnemonic::Jump, 0)?.opcode() as usize;
+                let back = self.new_label("ret");
+                self.emit(Mnemonic::LoadI, Some(Operand::Immediate(jump)));
+                self.emit(Mnemonic::MulI, Some(Operand::Immediate(10)));
+                self.emit(Mnemonic::MulI, Some(Operand::Immediate(10)));
+                self.emit(Mnemonic::AddI, Some(Operand::Label(back.clone())));
+                self.emit(Mnemonic::Store, Some(Operand::Label(exit_label(target))));
+                self.jump(Mnemonic::Jump, target);
+                self.push(ParsedLine::Label(back));
+            }
+            PseudoOp::If(condition) => {
+                let skip = self.new_label("else");
+                self.jump_unless(*condition, &skip);
+                self.blocks.push(Block::If {
+                    skip,
+                    has_else: false,
+                    line,
+                });
+            }
+            PseudoOp::Else => {
+                let Some(Block::If { skip, has_else, .. }) = self.blocks.last_mut() else {
+                    return Err(match self.blocks.last() {
+                        Some(block) => error(
+                            line,
+                            format!(
+                                "ELSE does not match {} on line {}",
+                                block.opener(),
+                                block.line()
+                            ),
+                        ),
+                        None => error(line, "ELSE without IF"),
+                    });
+                };
+                if *has_else {
+                    return Err(error(line, "IF already has an ELSE"));
+                }
+                *has_else = true;
+                let else_label = skip.clone();
+
+                let end = self.new_label("endif");
+                self.jump(Mnemonic::Jump, &end);
+                self.push(ParsedLine::Label(else_label));
+                if let Some(Block::If { skip, .. }) = self.blocks.last_mut() {
+                    *skip = end;
+                }
+            }
+            PseudoOp::EndIf => {
+                let Block::If { skip, .. } = self.close("ENDIF", "IF")? else {
+                    unreachable!("close checks the block kind");
+                };
+                self.push(ParsedLine::Label(skip));
+            }
+            PseudoOp::While(condition) => {
+                let top = self.new_label("while");
+                let end = self.new_label("endw");
+                self.push(ParsedLine::Label(top.clone()));
+                self.jump_unless(*condition, &end);
+                self.blocks.push(Block::While { top, end, line });
+            }
+            PseudoOp::EndWhile => {
+                let Block::While { top, end, .. } = self.close("ENDW", "WHILE")? else {
+                    unreachable!("close checks the block kind");
+                };
+                self.jump(Mnemonic::Jump, &top);
+                self.push(ParsedLine::Label(end));
+            }
+            PseudoOp::Repeat => {
+                let top = self.new_label("repeat");
+                self.push(ParsedLine::Label(top.clone()));
+                self.blocks.push(Block::Repeat { top, line });
+            }
+            PseudoOp::Until(condition) => {
+                let Block::Repeat { top, .. } = self.close("UNTIL", "REPEAT")? else {
+                    unreachable!("close checks the block kind");
+                };
+                self.jump_unless(*condition, &top);
+            }
+            PseudoOp::Inc(name) | PseudoOp::Dec(name) => {
+                let step = if matches!(op, PseudoOp::Inc(_)) {
+                    Mnemonic::AddI
+                } else {
+                    Mnemonic::SubI
+                };
+                self.var(Mnemonic::LoadM, name);
+                self.immediate(step, 1);
+                self.var(Mnemonic::Store, name);
+            }
+            PseudoOp::Neg => self.negate(),
+            PseudoOp::Clr(name) => {
+                self.immediate(Mnemonic::LoadI, 0);
+                self.var(Mnemonic::Store, name);
+            }
+            PseudoOp::Mov(dst, src) => {
+                self.var(Mnemonic::LoadM, src);
+                self.var(Mnemonic::Store, dst);
+            }
+            PseudoOp::Swap(a, b) => {
+                self.uses_scratch = true;
+                self.var(Mnemonic::LoadM, a);
+                self.var(Mnemonic::Store, SCRATCH);
+                self.var(Mnemonic::LoadM, b);
+                self.var(Mnemonic::Store, a);
+                self.var(Mnemonic::LoadM, SCRATCH);
+                self.var(Mnemonic::Store, b);
+            }
+            PseudoOp::Abs => {
+                let negative = self.new_label("abs");
+                let done = self.new_label("abs_end");
+                self.jump(Mnemonic::JumpIfNegative, &negative);
+                self.jump(Mnemonic::Jump, &done);
+                self.push(ParsedLine::Label(negative));
+                self.negate();
+                self.push(ParsedLine::Label(done));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replaces pseudo-ops in place with the instructions they stand for.
+/// Expanded instructions keep the line number of the pseudo-op.
+///
+/// Subroutines use the classic self-modifying return: `CALL f` builds the
+/// word `JMP <return address>`, stores it into `f`'s exit cell and jumps to
+/// `f`; `RET` jumps to the exit cell. `CALL` therefore overwrites the
+/// accumulator, and a procedure cannot call itself.
+///
+/// Block constructs test the accumulator and lower to the inverse jump
+/// around the block, so they nest like ordinary structured code.
+///
+/// `INC`, `DEC`, `CLR`, `MOV` and `SWAP` go through the accumulator and
+/// leave it changed; `NEG`, `ABS` and `SWAP` use the [`SCRATCH`] cell.
+pub fn expand(
+    parsed: &mut Vec<ParsedLine>,
+    line_numbers: &mut Vec<usize>,
+    isa: &'static dyn InstructionSet,
+) -> Result<Expansion, SimpletronError> {
+    let procedures: Vec<&str> = parsed
+        .iter()
+        .filter_map(|line| match line {
+            ParsedLine::Pseudo(PseudoOp::Proc(name)) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let mut expander = Expander {
+        isa,
+        lines: Vec::with_capacity(parsed.len()),
+        line_numbers: Vec::with_capacity(parsed.len()),
+        line: 0,
+        labels: 0,
+        blocks: Vec::new(),
+        uses_scratch: false,
+        calls: Vec::new(),
+    };
+    let mut pseudo_lines = HashSet::new();
+
+    for (line, &number) in parsed.iter().zip(line_numbers.iter()) {
+        expander.line = number;
+        match line {
+            ParsedLine::Pseudo(op) => {
+                pseudo_lines.insert(number);
+                expander.expand(op, &procedures)?;
+            }
+            _ => expander.push(line.clone()),
+        }
+    }
+
+    if let Some(block) = expander.blocks.last() {
+        return Err(error(
+            block.line(),
+            format!("{} is never closed", block.opener()),
+        ));
+    }
+
+    check_recursion(&expander.calls)?;
+
+    if expander.uses_scratch {
+        expander.line = 0;
+        expander.push(ParsedLine::Variable(SCRATCH.to_string()));
+    }
+
+    let expansion = Expansion {
+        procedures: procedures
+            .iter()
+            .map(|name| (name.to_string(), exit_label(name)))
+            .collect(),
+        pseudo_lines,
+    };
+
+    *parsed = expander.lines;
+    *line_numbers = expander.line_numbers;
+    Ok(expansion)
+}
@@ -0,0 +1,540 @@
+use std::{
+    collections::{HashSet, hash_map::DefaultHasher},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    assembler::{
+        instruction::{AsmInstruction, Mnemonic, Operand},
+        parser::mnemonic_parser::{MnemonicParser, ParsedLine},
+    },
+    vm::{error::SimpletronError, overflow::WORD_LIMIT},
+};
+
+/// Values near the ends of a word, where overflow starts, that every
+/// candidate is also checked on.
+const WORD_BOUNDS: [i64; 12] = [
+    -9999, -9998, -5000, -4999, -200, -100, 100, 200, 4999, 5000, 9998, 9999,
+];
+
+#[derive(Debug, Clone)]
+pub struct SuperoptConfig {
+    /// Extra cells the replacement may use as temporaries.
+    pub scratch_cells: usize,
+    /// Immediates `0..=max_immediate` are tried, plus any in the fragment.
+    pub max_immediate: usize,
+    /// Whether the accumulator value after the fragment must be preserved.
+    pub acc_live: bool,
+    /// Upper bound on distinct search states before giving up.
+    pub max_states: usize,
+}
+
+impl Default for SuperoptConfig {
+    fn default() -> Self {
+        Self {
+            scratch_cells: 1,
+            max_immediate: 9,
+            acc_live: true,
+            max_states: 500_000,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SuperoptResult {
+    pub original: Vec<AsmInstruction>,
+    pub replacement: Option<Vec<AsmInstruction>>,
+    /// Inputs in `-confirmed_range..=confirmed_range` were checked
+    /// exhaustively, and so were combinations of [`WORD_BOUNDS`] values.
+    pub confirmed_range: i64,
+    pub explored: usize,
+}
+
+impl fmt::Display for SuperoptResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(replacement) = &self.replacement else {
+            return writeln!(
+                f,
+                "; no shorter sequence than {} instructions found ({} states explored)",
+                self.original.len(),
+                self.explored
+            );
+        };
+
+        writeln!(
+            f,
+            "; {} -> {} instructions, confirmed for all inputs in -{}..={} and near the word limits",
+            self.original.len(),
+            replacement.len(),
+            self.confirmed_range,
+            self.confirmed_range
+        )?;
+        // Scratch cells are the search's own, so declare the ones it used.
+        let mut scratch: Vec<&str> = replacement
+            .iter()
+            .filter_map(|instr| match &instr.operand {
+                Some(Operand::Variable(name)) if name.starts_with(SCRATCH_PREFIX) => {
+                    Some(name.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+        scratch.sort();
+        scratch.dedup();
+        for name in scratch {
+            writeln!(f, "VAR {}", name)?;
+        }
+        for instr in replacement {
+            writeln!(f, "{}", instr)?;
+        }
+        Ok(())
+    }
+}
+
+/// Names of the extra temporaries, followed by their index.
+const SCRATCH_PREFIX: &str = "_s";
+
+/// Machine state over the fragment's cells; `None` once it has faulted.
+type State = Option<(i64, Vec<i64>)>;
+
+#[derive(Debug, Clone)]
+struct Input {
+    acc: i64,
+    cells: Vec<i64>,
+}
+
+struct Search<'a> {
+    config: &'a SuperoptConfig,
+    named: usize,
+    alphabet: Vec<AsmInstruction>,
+    cell_names: Vec<String>,
+}
+
+/// Searches for the shortest straight-line sequence equivalent to `source`.
+///
+/// Candidates are found by breadth-first search over the states they produce
+/// on a set of test inputs, then confirmed by exhaustive evaluation over a
+/// bounded input range; counterexamples are added to the tests and the
+/// search is repeated.
+pub fn superoptimize(
+    source: &str,
+    config: &SuperoptConfig,
+) -> Result<SuperoptResult, SimpletronError> {
+    let original = parse_fragment(source)?;
+
+    let mut cell_names: Vec<String> = Vec::new();
+    let mut constants = Vec::new();
+    for instr in &original {
+        match &instr.operand {
+            Some(Operand::Variable(name)) if !cell_names.contains(name) => {
+                cell_names.push(name.clone())
+            }
+            Some(Operand::Immediate(value)) => constants.push(*value),
+            _ => {}
+        }
+    }
+    let named = cell_names.len();
+    cell_names.extend((0..config.scratch_cells).map(|i| format!("{}{}", SCRATCH_PREFIX, i)));
+
+    let search = Search {
+        config,
+        named,
+        alphabet: alphabet(&cell_names, config.max_immediate, &constants),
+        cell_names,
+    };
+
+    let mut tests = search.initial_tests();
+    let confirmed_range = search.confirmation_range();
+    let mut explored = 0;
+
+    for _ in 0..32 {
+        let target: Vec<State> = tests
+            .iter()
+            .map(|input| run(&search, &original, input))
+            .collect();
+        let (candidate, states) = search.bfs(&tests, &target, original.len().saturating_sub(1));
+        explored += states;
+
+        let Some(candidate) = candidate else {
+            break;
+        };
+
+        match search.confirm(&original, &candidate, confirmed_range) {
+            Some(counterexample) => tests.push(counterexample),
+            None => {
+                return Ok(SuperoptResult {
+                    original,
+                    replacement: Some(candidate),
+                    confirmed_range,
+                    explored,
+                });
+            }
+        }
+    }
+
+    Ok(SuperoptResult {
+        original,
+        replacement: None,
+        confirmed_range,
+        explored,
+    })
+}
+
+fn parse_fragment(source: &str) -> Result<Vec<AsmInstruction>, SimpletronError> {
+    let mut fragment = Vec::new();
+
+    for line in source.lines() {
+        match MnemonicParser::parse_line(line)? {
+            Some(ParsedLine::Instruction(instr)) => {
+                let supported = match (&instr.operand, is_immediate_op(instr.mnemonic)) {
+                    (Some(Operand::Variable(_)), false) => is_memory_arith(instr.mnemonic),
+                    (Some(Operand::Immediate(_)), true) => true,
+                    _ => false,
+                };
+                if !supported {
+                    return Err(SimpletronError::UnsupportedFragment(
+                        line.trim().to_string(),
+                    ));
+                }
+                fragment.push(instr);
+            }
+            Some(ParsedLine::Label(name)) => {
+                return Err(SimpletronError::UnsupportedFragment(format!("{}:", name)));
+            }
+            Some(ParsedLine::Pseudo(_) | ParsedLine::Directive(_)) => {
+                return Err(SimpletronError::UnsupportedFragment(
+                    line.trim().to_string(),
+                ));
+            }
+            Some(ParsedLine::Variable(_)) | None => {}
+        }
+    }
+
+    Ok(fragment)
+}
+
+fn is_memory_arith(mnemonic: Mnemonic) -> bool {
+    matches!(
+        mnemonic,
+        Mnemonic::LoadM
+            | Mnemonic::Store
+            | Mnemonic::AddM
+            | Mnemonic::SubM
+            | Mnemonic::MulM
+            | Mnemonic::DivM
+            | Mnemonic::ModM
+    )
+}
+
+fn is_immediate_op(mnemonic: Mnemonic) -> bool {
+    matches!(
+        mnemonic,
+        Mnemonic::LoadI
+            | Mnemonic::AddI
+            | Mnemonic::SubI
+            | Mnemonic::MulI
+            | Mnemonic::DivI
+            | Mnemonic::ModI
+    )
+}
+
+fn alphabet(cells: &[String], max_immediate: usize, constants: &[usize]) -> Vec<AsmInstruction> {
+    use Mnemonic::*;
+
+    let mut immediates: Vec<usize> = (0..=max_immediate)
+        .chain(constants.iter().copied())
+        .collect();
+    immediates.sort();
+    immediates.dedup();
+
+    let mut alphabet = Vec::new();
+    for mnemonic in [LoadM, Store, AddM, SubM, MulM, DivM, ModM] {
+        for name in cells {
+            alphabet.push(AsmInstruction {
+                mnemonic,
+                operand: Some(Operand::Variable(name.clone())),
+            });
+        }
+    }
+    for mnemonic in [LoadI, AddI, SubI, MulI, DivI, ModI] {
+        for value in &immediates {
+            let noop = matches!(
+                (mnemonic, value),
+                (AddI | SubI, 0) | (MulI | DivI, 1) | (DivI | ModI, 0)
+            );
+            if !noop {
+                alphabet.push(AsmInstruction {
+                    mnemonic,
+                    operand: Some(Operand::Immediate(*value)),
+                });
+            }
+        }
+    }
+    alphabet
+}
+
+fn step(search: &Search, state: &mut State, instr: &AsmInstruction) {
+    let Some((acc, cells)) = state else {
+        return;
+    };
+
+    let value = match &instr.operand {
+        Some(Operand::Variable(name)) => {
+            let index = search
+                .cell_names
+                .iter()
+                .position(|cell| cell == name)
+                .unwrap();
+            if instr.mnemonic == Mnemonic::Store {
+                cells[index] = *acc;
+                return;
+            }
+            cells[index]
+        }
+        Some(Operand::Immediate(value)) => *value as i64,
+        _ => return,
+    };
+
+    let result = match instr.mnemonic {
+        Mnemonic::LoadM | Mnemonic::LoadI => Some(value),
+        Mnemonic::AddM | Mnemonic::AddI => acc.checked_add(value),
+        Mnemonic::SubM | Mnemonic::SubI => acc.checked_sub(value),
+        Mnemonic::MulM | Mnemonic::MulI => acc.checked_mul(value),
+        Mnemonic::DivM | Mnemonic::DivI => acc.checked_div(value),
+        Mnemonic::ModM | Mnemonic::ModI => acc.checked_rem(value),
+        _ => Some(*acc),
+    };
+
+    // Results that do not fit a word trap, as under the default overflow
+    // policy, so faulting sequences only match faulting sequences.
+    match result {
+        Some(result) if (-WORD_LIMIT as i64..=WORD_LIMIT as i64).contains(&result) => *acc = result,
+        _ => *state = None,
+    }
+}
+
+fn run(search: &Search, code: &[AsmInstruction], input: &Input) -> State {
+    let mut state = Some((input.acc, input.cells.clone()));
+    for instr in code {
+        step(search, &mut state, instr);
+    }
+    state
+}
+
+impl Search<'_> {
+    /// Whether `actual` agrees with `expected` on everything observable.
+    fn matches(&self, expected: &State, actual: &State) -> bool {
+        match (expected, actual) {
+            (None, None) => true,
+            (Some((acc_a, cells_a)), Some((acc_b, cells_b))) => {
+                (!self.config.acc_live || acc_a == acc_b)
+                    && cells_a[..self.named] == cells_b[..self.named]
+            }
+            _ => false,
+        }
+    }
+
+    fn initial_tests(&self) -> Vec<Input> {
+        // Small deterministic LCG so runs are reproducible.
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            seed = seed
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            ((seed >> 33) % 201) as i64 - 100
+        };
+
+        let cells = self.cell_names.len();
+        let mut tests = vec![
+            Input {
+                acc: 0,
+                cells: vec![0; cells],
+            },
+            Input {
+                acc: 1,
+                cells: vec![1; cells],
+            },
+            Input {
+                acc: -1,
+                cells: vec![-1; cells],
+            },
+        ];
+        for _ in 0..13 {
+            tests.push(Input {
+                acc: next(),
+                cells: (0..cells).map(|_| next()).collect(),
+            });
+        }
+        for (index, bound) in WORD_BOUNDS.iter().enumerate() {
+            tests.push(Input {
+                acc: *bound,
+                cells: (0..cells)
+                    .map(|cell| WORD_BOUNDS[(index + cell + 1) % WORD_BOUNDS.len()])
+                    .collect(),
+            });
+        }
+        tests
+    }
+
+    /// Largest `r` so that every input in `-r..=r` can be checked cheaply.
+    fn confirmation_range(&self) -> i64 {
+        let dims = self.named as u32 + 1;
+        let mut range: i64 = 1;
+        while range < 64 && (2 * (range + 1) + 1).pow(dims) <= 200_000 {
+            range += 1;
+        }
+        range
+    }
+
+    fn confirm(
+        &self,
+        original: &[AsmInstruction],
+        candidate: &[AsmInstruction],
+        range: i64,
+    ) -> Option<Input> {
+        let small: Vec<i64> = (-range..=range).collect();
+        // Word bounds mixed with small values, so that both an operand
+        // near the limit and one that pushes a result over it come up.
+        let mut bounds: Vec<i64> = WORD_BOUNDS.to_vec();
+        bounds.extend([-2, -1, 0, 1, 2, 50]);
+
+        self.confirm_over(original, candidate, &small)
+            .or_else(|| self.confirm_over(original, candidate, &bounds))
+    }
+
+    /// Checks every combination of `values` for the accumulator and the
+    /// named cells.
+    fn confirm_over(
+        &self,
+        original: &[AsmInstruction],
+        candidate: &[AsmInstruction],
+        choices: &[i64],
+    ) -> Option<Input> {
+        let dims = self.named + 1;
+        let width = choices.len();
+        let total = width.saturating_pow(dims as u32).min(1_000_000);
+
+        for scratch in [0, 4242] {
+            for combo in 0..total {
+                let mut rest = combo;
+                let mut values = Vec::with_capacity(dims);
+                for _ in 0..dims {
+                    values.push(choices[rest % width]);
+                    rest /= width;
+                }
+
+                let mut cells = values[1..].to_vec();
+                cells.resize(self.cell_names.len(), scratch);
+                let input = Input {
+                    acc: values[0],
+                    cells,
+                };
+
+                let expected = run(self, original, &input);
+                if !self.matches(&expected, &run(self, candidate, &input)) {
+                    return Some(input);
+                }
+            }
+        }
+        None
+    }
+
+    fn fingerprint(states: &[State]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        states.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Breadth-first search over distinct test-state fingerprints. Returns
+    /// the first sequence matching `target` and the number of states seen.
+    fn bfs(
+        &self,
+        tests: &[Input],
+        target: &[State],
+        max_len: usize,
+    ) -> (Option<Vec<AsmInstruction>>, usize) {
+        // Nodes are (parent, alphabet index); node 0 is the empty sequence.
+        let mut nodes: Vec<(usize, usize)> = vec![(usize::MAX, usize::MAX)];
+        let mut seen = HashSet::new();
+
+        let initial: Vec<State> = tests
+            .iter()
+            .map(|input| Some((input.acc, input.cells.clone())))
+            .collect();
+        if self.goal(&initial, target) {
+            return (Some(Vec::new()), 1);
+        }
+        seen.insert(Self::fingerprint(&initial));
+
+        let mut frontier = vec![0];
+        for _ in 0..max_len {
+            let mut next_frontier = Vec::new();
+
+            for &node in &frontier {
+                let path = self.path(&nodes, node);
+                let states: Vec<State> =
+                    tests.iter().map(|input| run(self, &path, input)).collect();
+
+                for (index, instr) in self.alphabet.iter().enumerate() {
+                    if path.last().is_some_and(|last| redundant(last, instr)) {
+                        continue;
+                    }
+
+                    let mut after = states.clone();
+                    for state in after.iter_mut() {
+                        step(self, state, instr);
+                    }
+                    if !seen.insert(Self::fingerprint(&after)) {
+                        continue;
+                    }
+
+                    nodes.push((node, index));
+                    if self.goal(&after, target) {
+                        return (Some(self.path(&nodes, nodes.len() - 1)), seen.len());
+                    }
+                    next_frontier.push(nodes.len() - 1);
+
+                    if seen.len() >= self.config.max_states {
+                        return (None, seen.len());
+                    }
+                }
+            }
+
+            frontier = next_frontier;
+        }
+
+        (None, seen.len())
+    }
+
+    fn goal(&self, states: &[State], target: &[State]) -> bool {
+        states
+            .iter()
+            .zip(target)
+            .all(|(actual, expected)| self.matches(expected, actual))
+    }
+
+    fn path(&self, nodes: &[(usize, usize)], mut node: usize) -> Vec<AsmInstruction> {
+        let mut path = Vec::new();
+        while node != 0 {
+            let (parent, index) = nodes[node];
+            path.push(self.alphabet[index].clone());
+            node = parent;
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// Pairs where the first instruction can never matter.
+fn redundant(prev: &AsmInstruction, next: &AsmInstruction) -> bool {
+    let overwrites_acc = matches!(next.mnemonic, Mnemonic::LoadM | Mnemonic::LoadI);
+    // Arithmetic can fault by dividing by zero or overflowing, so it is
+    // not dead even when its result is.
+    let prev_only_sets_acc = matches!(prev.mnemonic, Mnemonic::LoadM | Mnemonic::LoadI);
+    let same_operand = prev.operand == next.operand;
+
+    (overwrites_acc && prev_only_sets_acc)
+        || (prev.mnemonic == Mnemonic::Store && next.mnemonic == Mnemonic::Store && same_operand)
+        || (prev.mnemonic == Mnemonic::Store && next.mnemonic == Mnemonic::LoadM && same_operand)
+}
@@ -0,0 +1,43 @@
+use std::fmt::Write;
+
+use crate::assembler::AssembledProgram;
+
+/// Renders a symbol file: the entry point, then one line per label,
+/// variable and procedure, sorted by address. Procedures list their entry
+/// and exit cells.
+///
+/// ```text
+/// START  00
+/// LABEL  03     loop
+/// PROC   10-14  square
+/// VAR    20     x
+/// ```
+pub fn render(program: &AssembledProgram) -> String {
+    let mut entries: Vec<(usize, String)> = Vec::new();
+
+    for (name, address) in &program.labels {
+        if !name.starts_with("__") {
+            entries.push((*address, format!("LABEL  {:0>2}     {}", address, name)));
+        }
+    }
+    for (name, address) in &program.vars {
+        entries.push((*address, format!("VAR    {:0>2}     {}", address, name)));
+    }
+    for procedure in &program.procedures {
+        entries.push((
+            procedure.start,
+            format!(
+                "PROC   {:0>2}-{:0>2}  {}",
+                procedure.start, procedure.end, procedure.name
+            ),
+        ));
+    }
+
+    entries.sort();
+    let mut out = String::new();
+    writeln!(out, "START  {:0>2}", program.entry).unwrap();
+    for (_, line) in entries {
+        writeln!(out, "{}", line).unwrap();
+    }
+    out
+}
@@ -0,0 +1,347 @@
+//! Translator from Brainfuck to mnemonic source.
+//!
+//! The tape starts at the `_tape` cell, the last variable, and runs to the
+//! end of memory. `_ptr` holds a ready-made `LOADM` word for the current
+//! cell; every access copies it into the instruction that follows (adding
+//! `_to_store` turns it into a `STORE`). `.` and `,` print and read whole
+//! numbers, and cells do not wrap at 256. Moving the pointer off either end
+//! of the tape stops the program with [`OFF_TAPE_STATUS`] instead of
+//! letting it patch its own code.
+
+use crate::{
+    assembler::{AssembledProgram, generated::GeneratedSource},
+    vm::{
+        error::SimpletronError,
+        isa::{InstructionSet, Simpletron},
+        operation::Opcode,
+    },
+};
+
+/// Instruction set the translated source is written for. The words it
+/// patches into its own code are built from this table.
+const ISA: &dyn InstructionSet = &Simpletron;
+
+/// Memory size the translated program has to fit in.
+pub const MEMORY_SIZE: usize = 100;
+
+/// Largest value an immediate operand can hold.
+const MAX_IMMEDIATE: i32 = 99;
+
+/// Where moves off the tape jump to.
+const OFF_TAPE: &str = "_off_tape";
+
+/// Exit status of a program that moved the pointer off the tape. It is the
+/// highest `HALT` status, so a translated program never stops with it
+/// otherwise.
+pub const OFF_TAPE_STATUS: u8 = 99;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    /// Run of `+` and `-`.
+    Add(i32),
+    /// Run of `>` and `<`.
+    Move(i32),
+    Output,
+    Input,
+    /// `[-]` or `[+]`.
+    Clear,
+    Open,
+    Close,
+}
+
+/// Parses the program into commands with their source lines, folding runs
+/// and checking that brackets balance.
+fn parse(source: &str) -> Result<Vec<(Command, usize)>, SimpletronError> {
+    let mut commands: Vec<(Command, usize)> = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        for c in text.chars() {
+            let command = match c {
+                '+' => Command::Add(1),
+                '-' => Command::Add(-1),
+                '>' => Command::Move(1),
+                '<' => Command::Move(-1),
+                '.' => Command::Output,
+                ',' => Command::Input,
+                '[' => Command::Open,
+                ']' => Command::Close,
+                _ => continue,
+            };
+
+            match (commands.last_mut(), command) {
+                (Some((Command::Add(total), _)), Command::Add(step))
+                | (Some((Command::Move(total), _)), Command::Move(step)) => {
+                    *total += step;
+                    if *total == 0 {
+                        commands.pop();
+                    }
+                }
+                (_, Command::Open) => {
+                    open.push(line);
+                    commands.push((command, line));
+                }
+                (_, Command::Close) => {
+                    if open.pop().is_none() {
+                        return Err(SimpletronError::CompileError {
+                            line,
+                            message: "`]` without matching `[`".to_string(),
+                        });
+                    }
+                    let len = commands.len();
+                    if len >= 2
+                        && commands[len - 2].0 == Command::Open
+                        && matches!(commands[len - 1].0, Command::Add(1 | -1))
+                    {
+                        commands.truncate(len - 2);
+                        commands.push((Command::Clear, line));
+                    } else {
+                        commands.push((command, line));
+                    }
+                }
+                _ => commands.push((command, line)),
+            }
+        }
+    }
+
+    match open.pop() {
+        Some(line) => Err(SimpletronError::CompileError {
+            line,
+            message: "`[` is never closed".to_string(),
+        }),
+        None => Ok(commands),
+    }
+}
+
+struct Translator {
+    code: GeneratedSource,
+    instructions: usize,
+    labels: usize,
+    loops: Vec<usize>,
+    line: usize,
+}
+
+impl Translator {
+    fn emit(&mut self, text: String) {
+        self.code.push(format!("    {}", text), self.line);
+        self.instructions += 1;
+    }
+
+    fn label(&mut self, name: &str) {
+        self.code.push(format!("{}:", name), self.line);
+    }
+
+    fn new_label(&mut self, prefix: &str) -> String {
+        self.labels += 1;
+        format!("_{}{}", prefix, self.labels)
+    }
+
+    /// Adds or subtracts `amount` in steps that fit an immediate operand.
+    fn add_immediate(&mut self, amount: i32) {
+        let mnemonic = if amount < 0 { "SUBI" } else { "ADDI" };
+        let mut remaining = amount.abs();
+        while remaining > 0 {
+            let step = remaining.min(MAX_IMMEDIATE);
+            self.emit(format!("{:<6} {}", mnemonic, step));
+            remaining -= step;
+        }
+    }
+
+    /// Leaves the word for `opcode` with a zero operand in the accumulator.
+    fn opcode_word(&mut self, opcode: i32) {
+        let (start, multiplications) = if opcode.abs() * 10 <= MAX_IMMEDIATE {
+            (opcode * 10, 1)
+        } else {
+            (opcode, 2)
+        };
+        if start < 0 {
+            self.emit("LOADI  0".to_string());
+            self.add_immediate(start);
+        } else {
+            self.emit(format!("LOADI  {}", start));
+        }
+        for _ in 0..multiplications {
+            self.emit("MULI   10".to_string());
+        }
+    }
+
+    /// Patches the instruction at `label` to load the current cell.
+    fn patch_load(&mut self, label: &str) {
+        self.emit("LOADM  _ptr".to_string());
+        self.emit(format!("STORE  {}", label));
+    }
+
+    /// Patches the instruction at `label` to store to the current cell.
+    fn patch_store(&mut self, label: &str) {
+        self.emit("LOADM  _ptr".to_string());
+        self.emit("ADDM   _to_store".to_string());
+        self.emit(format!("STORE  {}", label));
+    }
+
+    fn command(&mut self, command: Command) {
+        match command {
+            Command::Add(amount) => {
+                let load = self.new_label("load");
+                let store = self.new_label("store");
+                self.patch_load(&load);
+                self.patch_store(&store);
+                self.label(&load);
+                self.emit("LOADM  _tape".to_string());
+                self.add_immediate(amount);
+                self.label(&store);
+                self.emit("STORE  _tape".to_string());
+            }
+            Command::Move(amount) => {
+                self.emit("LOADM  _ptr".to_string());
+                self.add_immediate(amount);
+                self.emit("STORE  _ptr".to_string());
+                if amount > 0 {
+                    self.emit("SUBM   _last".to_string());
+                    self.emit(format!("JG     {}", OFF_TAPE));
+                } else {
+                    self.emit("SUBM   _first".to_string());
+                    self.emit(format!("JN     {}", OFF_TAPE));
+                }
+            }
+            Command::Output => {
+                let load = self.new_label("load");
+                self.patch_load(&load);
+                self.label(&load);
+                self.emit("LOADM  _tape".to_string());
+                self.emit("WRITEA".to_string());
+            }
+            Command::Input => {
+                let store = self.new_label("store");
+                self.patch_store(&store);
+                self.emit("READI".to_string());
+                self.label(&store);
+                self.emit("STORE  _tape".to_string());
+            }
+            Command::Clear => {
+                let store = self.new_label("store");
+                self.patch_store(&store);
+                self.emit("LOADI  0".to_string());
+                self.label(&store);
+                self.emit("STORE  _tape".to_string());
+            }
+            Command::Open => {
+                self.labels += 1;
+                let id = self.labels;
+                self.loops.push(id);
+                let load = self.new_label("load");
+                self.label(&format!("_loop{}", id));
+                self.patch_load(&load);
+                self.label(&load);
+                self.emit("LOADM  _tape".to_string());
+                self.emit(format!("JZ     _end{}", id));
+            }
+            Command::Close => {
+                let id = self.loops.pop().expect("brackets are balanced");
+                self.emit(format!("JMP    _loop{}", id));
+                self.label(&format!("_end{}", id));
+            }
+        }
+    }
+}
+
+fn generate(source: &str) -> Result<GeneratedSource, SimpletronError> {
+    let commands = parse(source)?;
+    let stores = commands
+        .iter()
+        .any(|(command, _)| matches!(command, Command::Add(_) | Command::Input | Command::Clear));
+    let moves = |right: bool| {
+        commands
+            .iter()
+            .any(|(command, _)| matches!(command, Command::Move(amount) if (*amount > 0) == right))
+    };
+    let (moves_right, moves_left) = (moves(true), moves(false));
+
+    let mut translator = Translator {
+        code: GeneratedSource::default(),
+        instructions: 0,
+        labels: 0,
+        loops: Vec::new(),
+        line: 0,
+    };
+
+    let load = ISA.encode(Opcode::LoadM, 0)?.opcode();
+    let store = ISA.encode(Opcode::Store, 0)?.opcode();
+
+    // _ptr = LOADM _tape.
+    translator.opcode_word(load);
+    translator.emit("ADDI   _tape".to_string());
+    translator.emit("STORE  _ptr".to_string());
+    if moves_left {
+        translator.emit("STORE  _first".to_string());
+    }
+    if moves_right {
+        // _last = LOADM for the last cell of memory.
+        translator.opcode_word(load);
+        translator.emit(format!("ADDI   {}", MEMORY_SIZE - 1));
+        translator.emit("STORE  _last".to_string());
+    }
+    if stores {
+        // _to_store = STORE x - LOADM x.
+        translator.opcode_word(store - load);
+        translator.emit("STORE  _to_store".to_string());
+    }
+
+    for (command, line) in commands {
+        translator.line = line;
+        translator.command(command);
+    }
+    translator.line = 0;
+    translator.emit("HALT".to_string());
+
+    if moves_left || moves_right {
+        translator.label(OFF_TAPE);
+        translator.emit(format!("HALT   {}", OFF_TAPE_STATUS));
+    }
+
+    // _ptr, the optional constants, and at least one tape cell.
+    let constants = [stores, moves_left, moves_right];
+    let data = 2 + constants.iter().filter(|used| **used).count();
+    let size = translator.instructions + data;
+    if size > MEMORY_SIZE {
+        return Err(SimpletronError::ProgramTooLarge {
+            size,
+            capacity: MEMORY_SIZE,
+        });
+    }
+
+    let mut generated = GeneratedSource::default();
+    generated.push("; generated from Brainfuck", 0);
+    generated.push(
+        format!(
+            "; tape: {} cells from _tape to the end of memory",
+            MEMORY_SIZE - size + 1
+        ),
+        0,
+    );
+    generated.push("VAR _ptr", 0);
+    if stores {
+        generated.push("VAR _to_store", 0);
+    }
+    if moves_left {
+        generated.push("VAR _first", 0);
+    }
+    if moves_right {
+        generated.push("VAR _last", 0);
+    }
+    generated.push("VAR _tape", 0);
+    generated.push("", 0);
+    generated.lines.extend(translator.code.lines);
+
+    Ok(generated)
+}
+
+/// Translates Brainfuck source to mnemonic source.
+pub fn translate(source: &str) -> Result<String, SimpletronError> {
+    Ok(generate(source)?.source())
+}
+
+/// Translates and assembles Brainfuck source.
+pub fn compile(source: &str) -> Result<AssembledProgram, SimpletronError> {
+    generate(source)?.assemble(source)
+}
@@ -1,11 +1,144 @@
+use std::time::Duration;
+
 use clap::Parser;
+
 #[derive(Parser, Debug)]
 #[command(name = "simpletron", version, about = "A virtual machine")]
 pub struct CliArgs {
-    /// Path to the sml file
+    /// Path to the program: mnemonic source, Simple source (`.simple`),
+    /// stack-language source (`.fth`) or Brainfuck (`.bf`)
     pub filename: String,
 
     /// Optional debugger to view the state of the memory and the cpu
     #[arg(long)]
     pub debug: bool,
+
+    /// Trace execution to stderr: `faults`, `io` (also every value read
+    /// and written) or `instructions` (also every instruction)
+    #[arg(long, value_name = "LEVEL", default_value = "off")]
+    pub trace: String,
+
+    /// Report how many instructions ran and how fast, on stderr
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Execute common instruction pairs as one superinstruction, e.g. to
+    /// compare `--stats` throughput
+    #[arg(long)]
+    pub fuse: bool,
+
+    /// Print only the numbers the program writes: no prompts, banner or
+    /// memory dump
+    #[arg(long)]
+    pub raw: bool,
+
+    /// Inputs for `READ`/`READI`, separated by commas (e.g. `--input 5,3,7`)
+    #[arg(
+        long,
+        value_name = "VALUES",
+        allow_hyphen_values = true,
+        conflicts_with = "input_file"
+    )]
+    pub input: Option<String>,
+
+    /// Read inputs from a file, separated by commas or whitespace
+    #[arg(long, value_name = "PATH")]
+    pub input_file: Option<String>,
+
+    /// Opcode table for mnemonic source: `simpletron` (the default) or
+    /// `sml`, the original twelve-instruction Simpletron Machine Language.
+    /// Compiled sources always use `simpletron`
+    #[arg(long, value_name = "NAME", default_value = "simpletron")]
+    pub isa: String,
+
+    /// Print the loaded memory image as instructions instead of running it
+    #[arg(long)]
+    pub disassemble: bool,
+
+    /// What to do when a result or input does not fit in a 4-digit word:
+    /// `trap` (the default), `wrap`, `saturate` or `unbounded`
+    #[arg(long, value_name = "POLICY", default_value = "trap")]
+    pub overflow: String,
+
+    /// Stop with a fault after executing this many instructions
+    #[arg(long, value_name = "N")]
+    pub max_steps: Option<u64>,
+
+    /// Stop with a fault after this many seconds of wall-clock time
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    pub timeout: Option<Duration>,
+
+    /// Stop with a fault as soon as the program repeats a state, which
+    /// means it can never halt
+    #[arg(long)]
+    pub detect_loops: bool,
+
+    /// Give a variable an initial value before the program runs; repeat for
+    /// several (e.g. `--set n=5 --set limit=100`)
+    #[arg(long, value_name = "NAME=VALUE", allow_hyphen_values = true)]
+    pub set: Vec<String>,
+
+    /// Print a variable's value when the program halts; repeat for several
+    #[arg(long, value_name = "NAME")]
+    pub get: Vec<String>,
+
+    /// Silence an assembler lint (e.g. `--allow dead-store`)
+    #[arg(long, value_name = "LINT")]
+    pub allow: Vec<String>,
+
+    /// Report an assembler lint as a warning
+    #[arg(long, value_name = "LINT")]
+    pub warn: Vec<String>,
+
+    /// Turn an assembler lint into an error
+    #[arg(long, value_name = "LINT")]
+    pub deny: Vec<String>,
+
+    /// Run the peephole and dead-code optimizer before encoding
+    #[arg(long)]
+    pub optimize: bool,
+
+    /// Comma-separated inputs used to check the optimized program against
+    /// the original; repeat for several runs (e.g. `--verify-with 5,3`)
+    #[arg(
+        long,
+        value_name = "INPUTS",
+        requires = "optimize",
+        allow_hyphen_values = true
+    )]
+    pub verify_with: Vec<String>,
+
+    /// Let variables with non-overlapping live ranges share a memory cell
+    #[arg(long)]
+    pub pack_vars: bool,
+
+    /// Print the assembled listing before running
+    #[arg(long)]
+    pub listing: bool,
+
+    /// Treat the file as a straight-line fragment and search for the
+    /// shortest equivalent sequence instead of running it
+    #[arg(long)]
+    pub superopt: bool,
+
+    /// Scratch cells the superoptimizer may use
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub scratch: usize,
+
+    /// Print the mnemonic source generated for a `.simple`, `.fth` or
+    /// `.bf` program instead of running it
+    #[arg(long)]
+    pub emit_asm: bool,
+
+    /// Write labels, variables and procedure boundaries to a symbol file
+    #[arg(long, value_name = "PATH")]
+    pub symbols: Option<String>,
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("{} is not a number of seconds", value))
 }
@@ -0,0 +1,600 @@
+use crate::{
+    assembler::generated::GeneratedSource,
+    forth::parser::{Program, Token},
+    vm::error::SimpletronError,
+};
+
+/// Words the compiler understands natively.
+pub const BUILTINS: [&str; 21] = [
+    "dup", "drop", "swap", "over", "+", "-", "*", "/", "mod", ".", "read", "=", "<", ">", "0=",
+    "0<", "if", "else", "then", "begin", "until",
+];
+
+/// Largest value an immediate operand can hold.
+const MAX_IMMEDIATE: i32 = 99;
+
+/// Largest magnitude a memory word can hold.
+const MAX_WORD: i32 = 9999;
+
+/// Scratch cell used by `swap` and negative literals.
+const TEMP: &str = "_tmp";
+
+/// Compile-time view of one data stack position. Every position `i` owns
+/// the memory cell `_s<i>`; literals stay symbolic until they have to be
+/// written there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    Slot,
+    Literal(i32),
+}
+
+enum Control {
+    If {
+        label: String,
+        depth: usize,
+        else_depth: Option<usize>,
+        line: usize,
+    },
+    Begin {
+        label: String,
+        depth: usize,
+        line: usize,
+    },
+}
+
+struct Compiler<'a> {
+    program: &'a Program,
+    code: Vec<(String, usize)>,
+    stack: Vec<Entry>,
+    /// Stack position whose cell currently equals the accumulator.
+    acc: Option<usize>,
+    control: Vec<Control>,
+    expanding: Vec<String>,
+    max_depth: usize,
+    uses_temp: bool,
+    labels: usize,
+    line: usize,
+}
+
+pub fn generate(program: &Program) -> Result<GeneratedSource, SimpletronError> {
+    let mut compiler = Compiler {
+        program,
+        code: Vec::new(),
+        stack: Vec::new(),
+        acc: None,
+        control: Vec::new(),
+        expanding: Vec::new(),
+        max_depth: 0,
+        uses_temp: false,
+        labels: 0,
+        line: 0,
+    };
+
+    compiler.words(&program.main)?;
+    if let Some(Control::If { line, .. } | Control::Begin { line, .. }) = compiler.control.last() {
+        return Err(error(*line, "control structure is never closed"));
+    }
+    compiler.emit("HALT".to_string());
+
+    let mut generated = GeneratedSource::default();
+    generated.push("; generated by the stack-language compiler", 0);
+    for index in 0..compiler.max_depth {
+        generated.push(format!("VAR {}", slot(index)), 0);
+    }
+    if compiler.uses_temp {
+        generated.push(format!("VAR {}", TEMP), 0);
+    }
+    generated.push("", 0);
+    generated.lines.extend(compiler.code);
+
+    Ok(generated)
+}
+
+fn error(line: usize, message: impl Into<String>) -> SimpletronError {
+    SimpletronError::CompileError {
+        line,
+        message: message.into(),
+    }
+}
+
+/// The sign of an entry, if it is known at compile time.
+fn is_negative(entry: Entry) -> Option<bool> {
+    match entry {
+        Entry::Literal(value) => Some(value < 0),
+        Entry::Slot => None,
+    }
+}
+
+fn slot(index: usize) -> String {
+    format!("_s{}", index)
+}
+
+impl Compiler<'_> {
+    fn emit(&mut self, text: String) {
+        self.code.push((format!("    {}", text), self.line));
+    }
+
+    fn label(&mut self, name: &str) {
+        self.code.push((format!("{}:", name), self.line));
+        self.acc = None;
+    }
+
+    fn new_label(&mut self, prefix: &str) -> String {
+        self.labels += 1;
+        format!("_{}{}", prefix, self.labels)
+    }
+
+    fn words(&mut self, tokens: &[Token]) -> Result<(), SimpletronError> {
+        for token in tokens {
+            self.line = token.line;
+            self.word(token)?;
+        }
+        Ok(())
+    }
+
+    fn word(&mut self, token: &Token) -> Result<(), SimpletronError> {
+        if let Ok(value) = token.text.parse::<i32>() {
+            if value.abs() > MAX_WORD {
+                return Err(error(
+                    token.line,
+                    format!("literal {} does not fit in a memory word", value),
+                ));
+            }
+            self.push(Entry::Literal(value));
+            return Ok(());
+        }
+
+        match token.text.as_str() {
+            "dup" => self.copy(1)?,
+            "over" => self.copy(2)?,
+            "drop" => {
+                self.pop()?;
+            }
+            "swap" => self.swap()?,
+            "+" | "-" | "*" | "/" | "mod" => self.binary(&token.text)?,
+            "=" | "<" | ">" => self.compare(&token.text)?,
+            "0=" | "0<" => self.compare_zero(&token.text)?,
+            "." => {
+                let index = self.require(1)?;
+                self.load(index, self.stack[index]);
+                self.pop()?;
+                self.emit("WRITEA".to_string());
+            }
+            "read" => {
+                let index = self.stack.len();
+                self.push(Entry::Slot);
+                self.emit(format!("READ   {}", slot(index)));
+            }
+            "if" => self.open_if()?,
+            "else" => self.open_else()?,
+            "then" => self.close_if()?,
+            "begin" => {
+                self.flush();
+                let label = self.new_label("begin");
+                self.label(&label);
+                self.control.push(Control::Begin {
+                    label,
+                    depth: self.stack.len(),
+                    line: self.line,
+                });
+            }
+            "until" => self.close_begin()?,
+            name => self.call(name)?,
+        }
+        Ok(())
+    }
+
+    /// Inlines a colon definition.
+    fn call(&mut self, name: &str) -> Result<(), SimpletronError> {
+        let program = self.program;
+        let definition = program
+            .definitions
+            .get(name)
+            .ok_or_else(|| error(self.line, format!("unknown word `{}`", name)))?;
+        if self.expanding.iter().any(|active| active == name) {
+            return Err(error(
+                self.line,
+                format!("recursive use of `{}` cannot be inlined", name),
+            ));
+        }
+
+        let line = self.line;
+        self.expanding.push(name.to_string());
+        self.words(&definition.body)?;
+        self.expanding.pop();
+        self.line = line;
+        Ok(())
+    }
+
+    fn push(&mut self, entry: Entry) {
+        self.stack.push(entry);
+        self.max_depth = self.max_depth.max(self.stack.len());
+    }
+
+    fn pop(&mut self) -> Result<Entry, SimpletronError> {
+        let entry = self
+            .stack
+            .pop()
+            .ok_or_else(|| error(self.line, "stack underflow"))?;
+        if self.acc == Some(self.stack.len()) {
+            self.acc = None;
+        }
+        Ok(entry)
+    }
+
+    fn require(&self, depth: usize) -> Result<usize, SimpletronError> {
+        if self.stack.len() < depth {
+            return Err(error(self.line, "stack underflow"));
+        }
+        Ok(self.stack.len() - depth)
+    }
+
+    fn load_literal(&mut self, value: i32) {
+        let magnitude = value.abs();
+        if magnitude <= MAX_IMMEDIATE {
+            self.emit(format!("LOADI  {}", magnitude));
+        } else {
+            for (index, digit) in magnitude.to_string().chars().enumerate() {
+                if index == 0 {
+                    self.emit(format!("LOADI  {}", digit));
+                } else {
+                    self.emit("MULI   10".to_string());
+                    self.emit(format!("ADDI   {}", digit));
+                }
+            }
+        }
+
+        if value < 0 {
+            self.uses_temp = true;
+            self.emit(format!("STORE  {}", TEMP));
+            self.emit("LOADI  0".to_string());
+            self.emit(format!("SUBM   {}", TEMP));
+        }
+        self.acc = None;
+    }
+
+    /// Loads the value at stack position `index` into the accumulator.
+    fn load(&mut self, index: usize, entry: Entry) {
+        match entry {
+            Entry::Literal(value) => self.load_literal(value),
+            Entry::Slot if self.acc == Some(index) => {}
+            Entry::Slot => {
+                self.emit(format!("LOADM  {}", slot(index)));
+                self.acc = Some(index);
+            }
+        }
+    }
+
+    fn store(&mut self, index: usize) {
+        self.emit(format!("STORE  {}", slot(index)));
+        self.stack[index] = Entry::Slot;
+        self.acc = Some(index);
+    }
+
+    /// Writes every pending literal to its cell so that all paths reaching
+    /// a label agree on the stack layout.
+    fn flush(&mut self) {
+        for index in 0..self.stack.len() {
+            if let Entry::Literal(value) = self.stack[index] {
+                self.load_literal(value);
+                self.store(index);
+            }
+        }
+    }
+
+    /// `dup` (`depth` 1) and `over` (`depth` 2).
+    fn copy(&mut self, depth: usize) -> Result<(), SimpletronError> {
+        let index = self.require(depth)?;
+        let entry = self.stack[index];
+        self.push(entry);
+        if entry == Entry::Slot {
+            self.load(index, entry);
+            self.store(self.stack.len() - 1);
+        }
+        Ok(())
+    }
+
+    fn swap(&mut self) -> Result<(), SimpletronError> {
+        let below = self.require(2)?;
+        let top = below + 1;
+        match (self.stack[below], self.stack[top]) {
+            (Entry::Slot, Entry::Literal(value)) => {
+                self.load(below, Entry::Slot);
+                self.store(top);
+                self.stack[below] = Entry::Literal(value);
+            }
+            (Entry::Literal(value), Entry::Slot) => {
+                self.load(top, Entry::Slot);
+                self.store(below);
+                self.stack[top] = Entry::Literal(value);
+            }
+            (Entry::Literal(_), Entry::Literal(_)) => self.stack.swap(below, top),
+            (Entry::Slot, Entry::Slot) => {
+                self.uses_temp = true;
+                self.load(below, Entry::Slot);
+                self.emit(format!("STORE  {}", TEMP));
+                self.load(top, Entry::Slot);
+                self.store(below);
+                self.emit(format!("LOADM  {}", TEMP));
+                self.store(top);
+            }
+        }
+        Ok(())
+    }
+
+    /// Leaves `below op top` in the accumulator, with `top` popped.
+    fn arithmetic(&mut self, op: &str) -> Result<usize, SimpletronError> {
+        let below = self.require(2)?;
+        let top = below + 1;
+
+        if let Entry::Literal(value) = self.stack[top]
+            && !(0..=MAX_IMMEDIATE).contains(&value)
+        {
+            self.load_literal(value);
+            self.store(top);
+        }
+
+        let (memory, immediate) = match op {
+            "+" => ("ADDM", "ADDI"),
+            "-" => ("SUBM", "SUBI"),
+            "*" => ("MULM", "MULI"),
+            "/" => ("DIVM", "DIVI"),
+            _ => ("MODM", "MODI"),
+        };
+
+        self.load(below, self.stack[below]);
+        match self.pop()? {
+            Entry::Literal(value) => self.emit(format!("{:<6} {}", immediate, value)),
+            Entry::Slot => self.emit(format!("{:<6} {}", memory, slot(top))),
+        }
+        self.acc = None;
+        Ok(below)
+    }
+
+    fn fold(&self, op: &str, left: i32, right: i32) -> Result<i32, SimpletronError> {
+        match op {
+            "+" => Ok(left + right),
+            "-" => Ok(left - right),
+            "*" => Ok(left * right),
+            "/" | "mod" if right == 0 => Err(error(self.line, "division by zero")),
+            "/" => Ok(left / right),
+            "mod" => Ok(left % right),
+            "=" => Ok((left == right) as i32),
+            "<" => Ok((left < right) as i32),
+            _ => Ok((left > right) as i32),
+        }
+    }
+
+    /// Replaces the top two entries with a folded literal when both are
+    /// known at compile time.
+    fn try_fold(&mut self, op: &str) -> Result<bool, SimpletronError> {
+        let below = self.require(2)?;
+        if let (Entry::Literal(left), Entry::Literal(right)) =
+            (self.stack[below], self.stack[below + 1])
+        {
+            let value = self.fold(op, left, right)?;
+            if value.abs() > MAX_WORD {
+                return Err(error(
+                    self.line,
+                    format!("constant {} does not fit in a memory word", value),
+                ));
+            }
+            self.stack.truncate(below);
+            self.push(Entry::Literal(value));
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn binary(&mut self, op: &str) -> Result<(), SimpletronError> {
+        if !self.try_fold(op)? {
+            let below = self.arithmetic(op)?;
+            self.store(below);
+        }
+        Ok(())
+    }
+
+    /// Compares the top two entries. Operands of opposite signs are ordered
+    /// by their signs alone; their difference is only computed when the
+    /// signs match, where it cannot overflow.
+    fn compare(&mut self, op: &str) -> Result<(), SimpletronError> {
+        if self.try_fold(op)? {
+            return Ok(());
+        }
+        let below = self.require(2)?;
+        let top = below + 1;
+
+        let truthy = self.new_label("true");
+        let falsy = self.new_label("false");
+        let same_sign = self.new_label("same");
+        let (less, greater) = match op {
+            "<" => (truthy.clone(), falsy.clone()),
+            ">" => (falsy.clone(), truthy.clone()),
+            _ => (falsy.clone(), falsy.clone()),
+        };
+
+        // Two literals were folded above, so at most one sign is known.
+        match (is_negative(self.stack[below]), is_negative(self.stack[top])) {
+            (None, Some(false)) => {
+                self.load(below, Entry::Slot);
+                self.emit(format!("JN     {}", less));
+            }
+            (None, Some(true)) => {
+                self.load(below, Entry::Slot);
+                self.emit(format!("JN     {}", same_sign));
+                self.emit(format!("JMP    {}", greater));
+            }
+            (Some(false), None) => {
+                self.load(top, Entry::Slot);
+                self.emit(format!("JN     {}", greater));
+            }
+            (Some(true), None) => {
+                self.load(top, Entry::Slot);
+                self.emit(format!("JN     {}", same_sign));
+                self.emit(format!("JMP    {}", less));
+            }
+            _ => {
+                let negative = self.new_label("negative");
+                self.load(below, Entry::Slot);
+                self.emit(format!("JN     {}", negative));
+                self.load(top, Entry::Slot);
+                self.emit(format!("JN     {}", greater));
+                self.emit(format!("JMP    {}", same_sign));
+                self.label(&negative);
+                self.load(top, Entry::Slot);
+                self.emit(format!("JN     {}", same_sign));
+                self.emit(format!("JMP    {}", less));
+            }
+        }
+
+        self.label(&same_sign);
+        let below = self.arithmetic("-")?;
+        let jump = match op {
+            "=" => "JZ",
+            "<" => "JN",
+            _ => "JG",
+        };
+        self.flag_to(jump, below, &truthy, Some(&falsy));
+        Ok(())
+    }
+
+    fn compare_zero(&mut self, op: &str) -> Result<(), SimpletronError> {
+        let index = self.require(1)?;
+        match self.stack[index] {
+            Entry::Literal(value) => {
+                let flag = if op == "0=" { value == 0 } else { value < 0 };
+                self.stack[index] = Entry::Literal(flag as i32);
+            }
+            Entry::Slot => {
+                self.load(index, Entry::Slot);
+                self.flag(if op == "0=" { "JZ" } else { "JN" }, index);
+            }
+        }
+        Ok(())
+    }
+
+    /// Turns the accumulator into 1 or 0 depending on `jump` and stores it
+    /// at stack position `index`.
+    fn flag(&mut self, jump: &str, index: usize) {
+        let truthy = self.new_label("true");
+        self.flag_to(jump, index, &truthy, None);
+    }
+
+    /// [`Self::flag`] with the labels of the 1 and, if earlier code jumps
+    /// there, the 0 branch.
+    fn flag_to(&mut self, jump: &str, index: usize, truthy: &str, falsy: Option<&str>) {
+        let done = self.new_label("done");
+        self.emit(format!("{:<6} {}", jump, truthy));
+        if let Some(falsy) = falsy {
+            self.label(falsy);
+        }
+        self.emit("LOADI  0".to_string());
+        self.emit(format!("JMP    {}", done));
+        self.label(truthy);
+        self.emit("LOADI  1".to_string());
+        self.label(&done);
+        self.store(index);
+    }
+
+    /// Flushes the stack, then pops the flag into the accumulator.
+    fn pop_flag(&mut self) -> Result<(), SimpletronError> {
+        let index = self.require(1)?;
+        self.flush();
+        self.load(index, Entry::Slot);
+        self.pop()?;
+        Ok(())
+    }
+
+    fn open_if(&mut self) -> Result<(), SimpletronError> {
+        self.pop_flag()?;
+        let label = self.new_label("else");
+        self.emit(format!("JZ     {}", label));
+        self.control.push(Control::If {
+            label,
+            depth: self.stack.len(),
+            else_depth: None,
+            line: self.line,
+        });
+        Ok(())
+    }
+
+    fn open_else(&mut self) -> Result<(), SimpletronError> {
+        let Some(Control::If {
+            label, else_depth, ..
+        }) = self.control.last()
+        else {
+            return Err(error(self.line, "`else` without `if`"));
+        };
+        if else_depth.is_some() {
+            return Err(error(self.line, "`if` already has an `else`"));
+        }
+        let else_label = label.clone();
+
+        self.flush();
+        let end = self.new_label("then");
+        self.emit(format!("JMP    {}", end));
+        self.label(&else_label);
+
+        let branch_depth = self.stack.len();
+        if let Some(Control::If {
+            label,
+            depth,
+            else_depth,
+            ..
+        }) = self.control.last_mut()
+        {
+            *label = end;
+            *else_depth = Some(branch_depth);
+            self.stack = vec![Entry::Slot; *depth];
+        }
+        Ok(())
+    }
+
+    fn close_if(&mut self) -> Result<(), SimpletronError> {
+        let Some(Control::If {
+            label,
+            depth,
+            else_depth,
+            ..
+        }) = self.control.pop()
+        else {
+            return Err(error(self.line, "`then` without `if`"));
+        };
+
+        self.flush();
+        self.label(&label);
+
+        let expected = else_depth.unwrap_or(depth);
+        if self.stack.len() != expected {
+            return Err(error(
+                self.line,
+                format!(
+                    "branches of `if` leave different stack depths ({} and {})",
+                    expected,
+                    self.stack.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn close_begin(&mut self) -> Result<(), SimpletronError> {
+        let Some(Control::Begin { label, depth, .. }) = self.control.pop() else {
+            return Err(error(self.line, "`until` without `begin`"));
+        };
+
+        self.pop_flag()?;
+        if self.stack.len() != depth {
+            return Err(error(
+                self.line,
+                format!(
+                    "loop body changes the stack depth from {} to {}",
+                    depth,
+                    self.stack.len()
+                ),
+            ));
+        }
+        self.emit(format!("JZ     {}", label));
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+//! Front end for a small Forth-like stack language. The data stack lives
+//! in memory cells `_s0`, `_s1`, ...; stack depth is tracked at compile
+//! time, so every control structure must leave it balanced. Colon
+//! definitions are inlined at each use.
+
+use crate::{assembler::AssembledProgram, vm::error::SimpletronError};
+
+pub mod codegen;
+pub mod parser;
+
+/// Compiles stack-language source to mnemonic source.
+pub fn compile_to_source(source: &str) -> Result<String, SimpletronError> {
+    let program = parser::parse(source, &codegen::BUILTINS)?;
+    Ok(codegen::generate(&program)?.source())
+}
+
+/// Compiles stack-language source and assembles it. The debug info points
+/// at the original source rather than the generated mnemonics.
+pub fn compile(source: &str) -> Result<AssembledProgram, SimpletronError> {
+    let program = parser::parse(source, &codegen::BUILTINS)?;
+    codegen::generate(&program)?.assemble(source)
+}
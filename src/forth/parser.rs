@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use crate::vm::error::SimpletronError;
+
+/// A whitespace-separated word of source, lowercased.
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub text: String,
+    /// 1-based source line the word appears on.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub body: Vec<Token>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub definitions: HashMap<String, Definition>,
+    /// Top-level words, executed in order.
+    pub main: Vec<Token>,
+}
+
+fn error(line: usize, message: impl Into<String>) -> SimpletronError {
+    SimpletronError::CompileError {
+        line,
+        message: message.into(),
+    }
+}
+
+/// Splits the source into words, dropping `\ ...` and `( ... )` comments.
+fn tokenize(source: &str) -> Result<Vec<Token>, SimpletronError> {
+    let mut tokens = Vec::new();
+    let mut comment_start = None;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        for word in text.split_whitespace() {
+            if comment_start.is_some() {
+                if word.ends_with(')') {
+                    comment_start = None;
+                }
+                continue;
+            }
+            match word {
+                "\\" => break,
+                "(" => comment_start = Some(line),
+                _ => tokens.push(Token {
+                    text: word.to_ascii_lowercase(),
+                    line,
+                }),
+            }
+        }
+    }
+
+    match comment_start {
+        Some(line) => Err(error(line, "unterminated `(` comment")),
+        None => Ok(tokens),
+    }
+}
+
+/// Parses the source into colon definitions and the top-level program.
+pub fn parse(source: &str, builtins: &[&str]) -> Result<Program, SimpletronError> {
+    let mut program = Program::default();
+    let mut current: Option<(String, Definition)> = None;
+    let mut tokens = tokenize(source)?.into_iter();
+
+    while let Some(token) = tokens.next() {
+        match token.text.as_str() {
+            ":" => {
+                if current.is_some() {
+                    return Err(error(token.line, "definitions cannot be nested"));
+                }
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| error(token.line, "expected a name after `:`"))?;
+                if builtins.contains(&name.text.as_str()) || name.text.parse::<i32>().is_ok() {
+                    return Err(error(name.line, format!("cannot redefine `{}`", name.text)));
+                }
+                if program.definitions.contains_key(&name.text) {
+                    return Err(error(
+                        name.line,
+                        format!("word `{}` is already defined", name.text),
+                    ));
+                }
+                current = Some((
+                    name.text,
+                    Definition {
+                        body: Vec::new(),
+                        line: token.line,
+                    },
+                ));
+            }
+            ";" => {
+                let (name, definition) = current
+                    .take()
+                    .ok_or_else(|| error(token.line, "`;` outside a definition"))?;
+                program.definitions.insert(name, definition);
+            }
+            _ => match &mut current {
+                Some((_, definition)) => definition.body.push(token),
+                None => program.main.push(token),
+            },
+        }
+    }
+
+    match current {
+        Some((name, definition)) => Err(error(
+            definition.line,
+            format!("definition of `{}` is missing `;`", name),
+        )),
+        None => Ok(program),
+    }
+}
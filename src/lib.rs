@@ -1,4 +1,7 @@
 pub mod assembler;
+pub mod brainfuck;
 pub mod cli;
+pub mod forth;
 pub mod orchestrator;
+pub mod simple;
 pub mod vm;
@@ -1,44 +1,364 @@
-use std::{fs::File, io::Read};
+use std::{
+    fs::{self, File},
+    io::Read,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
 
 use clap::Parser;
 use simpletron_rust::{
-    assembler,
+    assembler::{
+        self, AssembledProgram, AssemblerOptions, VarTable,
+        lint::{Lint, LintLevel},
+        listing,
+        superopt::{self, SuperoptConfig},
+        symbols,
+    },
+    brainfuck,
     cli::CliArgs,
+    forth,
     orchestrator::Orchestrator,
+    simple,
     vm::{
-        error::SimpletronError,
+        error::{SETUP_EXIT_CODE, SimpletronError},
+        io::{self as vm_io, ConsoleIo, IoDevice, OutputFormat, ScriptedIo},
+        isa::{self, InstructionSet, Simpletron},
+        limits::Limits,
         memory::{MemoryLoader, SimpleMemory},
+        overflow::{OverflowPolicy, WORD_LIMIT},
         processor::SimpleProcessor,
+        trace::{PrintTrace, StepDebugger, TraceLevel, TraceSink},
     },
 };
 
-fn main() {
-    let args = CliArgs::parse();
+fn main() -> ExitCode {
+    // clap exits with 2 on bad arguments, which a script could not tell
+    // apart from `HALT 2`.
+    let args = match CliArgs::try_parse() {
+        Ok(args) => args,
+        Err(err) => {
+            let _ = err.print();
+            return if err.use_stderr() {
+                ExitCode::from(SETUP_EXIT_CODE)
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
 
-    if let Err(err) = run(args) {
-        eprintln!("error: {}", err);
-        std::process::exit(1);
+    match run(args) {
+        Ok(status) => ExitCode::from(status),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
     }
 }
 
-fn run(args: CliArgs) -> Result<(), SimpletronError> {
-    let path = args.filename;
-
+/// Runs the CLI and returns the program's exit status.
+fn run(args: CliArgs) -> Result<u8, SimpletronError> {
     let mut source = String::new();
-    File::open(path)?.read_to_string(&mut source)?;
+    File::open(&args.filename)?.read_to_string(&mut source)?;
 
-    let program = assembler::assemble(&source)?;
+    if args.superopt {
+        let config = SuperoptConfig {
+            scratch_cells: args.scratch,
+            ..Default::default()
+        };
+        print!("{}", superopt::superoptimize(&source, &config)?);
+        return Ok(0);
+    }
+
+    let front_end = FrontEnd::for_file(&args.filename);
+    if args.emit_asm {
+        let generated = match front_end {
+            FrontEnd::Simple => simple::compile_to_source(&source)?,
+            FrontEnd::Forth => forth::compile_to_source(&source)?,
+            FrontEnd::Brainfuck => brainfuck::translate(&source)?,
+            FrontEnd::Assembler => {
+                return Err(SimpletronError::UnsupportedFrontEnd(args.filename.clone()));
+            }
+        };
+        print!("{}", generated);
+        return Ok(0);
+    }
+
+    let isa: &'static dyn InstructionSet = match front_end {
+        FrontEnd::Assembler => isa::by_name(&args.isa)?,
+        _ => &Simpletron,
+    };
+    let (words, debug_info, entry, vars) = match front_end {
+        FrontEnd::Simple => {
+            let program = simple::compile(&source)?;
+            (program.words, program.debug_info, 0, program.vars)
+        }
+        FrontEnd::Forth | FrontEnd::Brainfuck => {
+            let program = match front_end {
+                FrontEnd::Forth => forth::compile(&source)?,
+                _ => brainfuck::compile(&source)?,
+            };
+            if args.listing {
+                println!("{}", listing::render(&program));
+            }
+            (
+                program.words,
+                program.debug_info,
+                program.entry,
+                program.vars,
+            )
+        }
+        FrontEnd::Assembler => {
+            let program = assemble(&args, &source, isa)?;
+            (
+                program.words,
+                program.debug_info,
+                program.entry,
+                program.vars,
+            )
+        }
+    };
+    if args.disassemble {
+        print!("{}", disassemble(isa, &words));
+        return Ok(0);
+    }
+
+    let overflow: OverflowPolicy = args.overflow.parse()?;
+    let trace: TraceLevel = args.trace.parse()?;
+    let settings = settings(&args.set, &vars)?;
+    let watched = args
+        .get
+        .iter()
+        .map(|name| variable(&vars, name).map(|address| (name, address)))
+        .collect::<Result<Vec<_>, _>>()?;
 
     let mut memory = SimpleMemory::new(None);
     {
         let mut loader = MemoryLoader::new(&mut memory, args.debug);
-        loader.load_program(&program)?;
-        println!("{:?}", program)
+        loader.load_program(&words)?;
+        if !args.raw {
+            println!("{:?}", words)
+        }
     }
 
+    let format = if args.raw {
+        OutputFormat::Raw
+    } else {
+        OutputFormat::Labeled
+    };
+    let device: Box<dyn IoDevice> = match scripted_inputs(&args)? {
+        Some(inputs) => Box::new(ScriptedIo::new(inputs, format)),
+        None => Box::new(ConsoleIo::new(format)),
+    };
     let cpu = SimpleProcessor::new();
-    let mut controller = Orchestrator::new(cpu, memory, args.debug);
-    controller.run()?;
+    let mut controller = Orchestrator::with_io(cpu, memory, device);
+    controller.set_quiet(args.raw);
+    controller.set_debug_info(debug_info);
+    controller.set_entry(entry)?;
+    controller.set_overflow(overflow);
+    controller.set_instruction_set(isa);
+    controller.machine_mut().set_fusion(args.fuse);
+    controller.set_limits(Limits {
+        max_steps: args.max_steps,
+        timeout: args.timeout,
+        detect_loops: args.detect_loops,
+    });
+    for &(address, value) in &settings {
+        controller.machine_mut().write_memory(address, value)?;
+    }
+
+    if args.debug {
+        execute(controller.with_trace(StepDebugger), &watched, args.stats)
+    } else if trace > TraceLevel::Off {
+        execute(
+            controller.with_trace(PrintTrace::new(trace)),
+            &watched,
+            args.stats,
+        )
+    } else {
+        execute(controller, &watched, args.stats)
+    }
+}
+
+/// Runs the loaded program, then prints `--get` variables, `--stats` and a
+/// warning about unread input.
+fn execute<T: TraceSink>(
+    mut controller: Orchestrator<SimpleProcessor, SimpleMemory, Box<dyn IoDevice>, T>,
+    watched: &[(&String, usize)],
+    stats: bool,
+) -> Result<u8, SimpletronError> {
+    let started = Instant::now();
+    let result = controller.run();
+    if stats {
+        report_stats(controller.machine().steps(), started.elapsed());
+    }
+    let status = result?;
+
+    for &(name, address) in watched {
+        println!("{} = {}", name, controller.machine().read_memory(address)?);
+    }
+
+    let unread = controller.io_mut().unread();
+    if unread > 0 {
+        eprintln!(
+            "warning: {} input value{} not read",
+            unread,
+            if unread == 1 { " was" } else { "s were" }
+        );
+    }
+    Ok(status)
+}
+
+fn report_stats(steps: u64, elapsed: Duration) {
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 {
+        steps as f64 / seconds
+    } else {
+        0.0
+    };
+    eprintln!(
+        "executed {} instructions in {:.3}s ({:.0} instructions/s)",
+        steps, seconds, rate
+    );
+}
+
+/// Inputs given up front with `--input` or `--input-file`, or `None` to
+/// read stdin as the program asks for values.
+fn scripted_inputs(args: &CliArgs) -> Result<Option<Vec<i32>>, SimpletronError> {
+    if let Some(list) = &args.input {
+        return parse_inputs(list).map(Some);
+    }
+    if let Some(path) = &args.input_file {
+        return vm_io::parse_values(&fs::read_to_string(path)?).map(Some);
+    }
+    Ok(None)
+}
+
+/// Source language, chosen by file extension.
+enum FrontEnd {
+    Assembler,
+    Simple,
+    Forth,
+    Brainfuck,
+}
+
+impl FrontEnd {
+    fn for_file(filename: &str) -> Self {
+        match filename.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("simple") => FrontEnd::Simple,
+            Some("fth" | "fs") => FrontEnd::Forth,
+            Some("bf") => FrontEnd::Brainfuck,
+            _ => FrontEnd::Assembler,
+        }
+    }
+}
+
+fn assemble(
+    args: &CliArgs,
+    source: &str,
+    isa: &'static dyn InstructionSet,
+) -> Result<AssembledProgram, SimpletronError> {
+    let mut options = assembler_options(args)?;
+    options.instruction_set = isa;
+    let program = assembler::assemble_with(source, &options)?;
+    for warning in &program.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    if let Some(report) = &program.optimization {
+        eprintln!("{}", report);
+    }
+    if args.listing {
+        println!("{}", listing::render(&program));
+    }
+    if let Some(path) = &args.symbols {
+        fs::write(path, symbols::render(&program))?;
+    }
+    Ok(program)
+}
+
+/// One line per non-empty cell: address, word and, if the word decodes,
+/// the instruction.
+fn disassemble(isa: &dyn InstructionSet, words: &[u16]) -> String {
+    let mut out = String::from("ADDR  WORD   INSTRUCTION\n");
+    for (address, &word) in words.iter().enumerate().filter(|(_, word)| **word != 0) {
+        let text = isa.disassemble(word.into()).unwrap_or_default();
+        out.push_str(&format!("{:0>2}    +{:0>4}  {}\n", address, word, text));
+    }
+    out
+}
+
+fn assembler_options(args: &CliArgs) -> Result<AssemblerOptions, SimpletronError> {
+    let mut options = AssemblerOptions::default();
+
+    let levels = [
+        (&args.allow, LintLevel::Allow),
+        (&args.warn, LintLevel::Warn),
+        (&args.deny, LintLevel::Deny),
+    ];
+    for (names, level) in levels {
+        for name in names {
+            options.lints.set(name.parse::<Lint>()?, level);
+        }
+    }
+
+    options.optimize = args.optimize;
+    options.pack_variables = args.pack_vars;
+    // --set and --get address a variable's own cell, not one it shares.
+    options.separate_variables = args
+        .set
+        .iter()
+        .filter_map(|setting| setting.split_once('='))
+        .map(|(name, _)| name.trim().to_string())
+        .chain(args.get.iter().cloned())
+        .collect();
+    for inputs in &args.verify_with {
+        options.verify_inputs.push(parse_inputs(inputs)?);
+    }
+
+    Ok(options)
+}
+
+/// Resolves `--set NAME=VALUE` options to the cells and values to store.
+fn settings(settings: &[String], vars: &VarTable) -> Result<Vec<(usize, i32)>, SimpletronError> {
+    settings
+        .iter()
+        .map(|setting| {
+            let invalid = |message: &str| SimpletronError::InvalidSetting {
+                setting: setting.clone(),
+                message: message.to_string(),
+            };
+            let (name, value) = setting
+                .split_once('=')
+                .ok_or_else(|| invalid("expected NAME=VALUE"))?;
+            let address = variable(vars, name.trim())?;
+            let value: i32 = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid("the value is not a number"))?;
+            if !(-WORD_LIMIT..=WORD_LIMIT).contains(&value) {
+                return Err(invalid(&format!(
+                    "the value must be between -{} and {}",
+                    WORD_LIMIT, WORD_LIMIT
+                )));
+            }
+            Ok((address, value))
+        })
+        .collect()
+}
+
+fn variable(vars: &VarTable, name: &str) -> Result<usize, SimpletronError> {
+    vars.get(name)
+        .copied()
+        .ok_or_else(|| SimpletronError::UnknownVariable(name.to_string()))
+}
 
-    Ok(())
+fn parse_inputs(list: &str) -> Result<Vec<i32>, SimpletronError> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| SimpletronError::InvalidReadInput(value.to_string()))
+        })
+        .collect()
 }
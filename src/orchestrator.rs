@@ -1,19 +1,32 @@
+use crate::vm::debug_info::DebugInfo;
 use crate::vm::error::SimpletronError;
-use crate::vm::instruction::Instruction;
-use crate::vm::loader::ParsedInstruction;
-use crate::vm::memory::{MemoryData, MemoryInterface, MemoryPayload};
+use crate::vm::io::{ConsoleIo, IoDevice};
+use crate::vm::isa::InstructionSet;
+use crate::vm::limits::Limits;
+use crate::vm::machine::{Machine, StepOutcome};
+use crate::vm::memory::MemoryInterface;
+use crate::vm::overflow::OverflowPolicy;
 use crate::vm::processor::ProcessorInterface;
+use crate::vm::trace::{NoTrace, TraceEvent, TraceLevel, TraceSink};
 
-use std::io::{self, Write};
+/// Instructions run between output flushes when nothing traces each
+/// instruction.
+const BATCH_STEPS: usize = 4096;
 
-pub struct Orchestrator<P, M>
+/// Runs a [`Machine`] against an [`IoDevice`], the console by default, and
+/// reports what happens to a [`TraceSink`], none by default.
+pub struct Orchestrator<P, M, I = ConsoleIo, T = NoTrace>
 where
     P: ProcessorInterface,
     M: MemoryInterface,
+    I: IoDevice,
+    T: TraceSink,
 {
-    cpu: P,
-    memory: M,
-    debug: bool,
+    machine: Machine<P, M>,
+    io: I,
+    trace: T,
+    quiet: bool,
+    inputs_read: usize,
 }
 
 impl<P, M> Orchestrator<P, M>
@@ -21,422 +34,173 @@ where
     P: ProcessorInterface,
     M: MemoryInterface,
 {
-    pub fn new(cpu: P, memory: M, debug: bool) -> Self {
-        Self { cpu, memory, debug }
+    pub fn new(cpu: P, memory: M) -> Self {
+        Self::with_io(cpu, memory, ConsoleIo::default())
     }
 }
 
-impl<P, M> Orchestrator<P, M>
+impl<P, M, I> Orchestrator<P, M, I>
 where
     P: ProcessorInterface,
     M: MemoryInterface,
+    I: IoDevice,
 {
-    pub fn run(&mut self) -> Result<(), SimpletronError> {
-        println!("*** Welcome to Simpletron ***");
-        println!("*** Program Loaded Succesfully ***\n");
-        loop {
-            let (address, data) = self.fetch_instruction()?;
-            let parsed_instr = ParsedInstruction { address, data };
-            self.cpu.update_state(&parsed_instr)?;
-
-            if self.debug {
-                println!("");
-                self.cpu.dump();
-                self.memory.dump(self.cpu.get_pc() as isize);
-            }
-            self.execute(Instruction::try_from(parsed_instr)?)?;
-
-            if self.debug {
-                wait_for_keypress();
-            }
-        }
-
-        fn wait_for_keypress() {
-            println!("\nPress Enter to continue...");
-            let mut buf = String::new();
-            let _ = io::stdin().read_line(&mut buf);
-        }
-    }
-
-    fn fetch_instruction(&self) -> Result<(usize, String), SimpletronError> {
-        let address = self.cpu.get_pc();
-        let data = self.memory.read_data(address)?;
-        Ok((address, data))
-    }
-
-    pub fn execute(&mut self, instr: Instruction) -> Result<(), SimpletronError> {
-        use crate::vm::operation::Opcode::*;
-
-        match instr.opcode {
-            Read => self.read(instr.operand, self.debug)?,
-            Write => self.write(instr.operand, self.debug)?,
-            WriteAcc => self.write_acc(self.debug)?,
-            ReadI => self.read_i(self.debug)?,
-            LoadM => self.load_m(instr.operand, self.debug)?,
-            Store => self.store(instr.operand, self.debug)?,
-            LoadI => self.load_i(instr.operand, self.debug)?,
-            AddM => self.add_m(instr.operand, self.debug)?,
-            SubM => self.sub_m(instr.operand, self.debug)?,
-            DivM => self.div_m(instr.operand, self.debug)?,
-            ModM => self.mod_m(instr.operand, self.debug)?,
-            MulM => self.mul_m(instr.operand, self.debug)?,
-            AddI => self.add_i(instr.operand, self.debug)?,
-            SubI => self.sub_i(instr.operand, self.debug)?,
-            DivI => self.div_i(instr.operand, self.debug)?,
-            ModI => self.mod_i(instr.operand, self.debug)?,
-            MulI => self.mul_i(instr.operand, self.debug)?,
-            Jump => self.jump(instr.operand, self.debug)?,
-            JumpIfNegative => self.jump_if_negative(instr.operand, self.debug)?,
-            JumpIfZero => self.jump_if_zero(instr.operand, self.debug)?,
-            Halt => self.halt(self.debug)?,
-            JumpIfNotZero => self.jump_if_not_zero(instr.operand, self.debug)?,
-            JumpIfGreaterThanZero => self.jump_if_greater_than_zero(instr.operand, self.debug)?,
-        }
-        Ok(())
-    }
-
-    fn debug(&self, debug: bool, msg: impl AsRef<str>) {
-        if debug {
-            println!("{}", "-".repeat(100));
-            println!("[DEBUG] {}", msg.as_ref());
+    pub fn with_io(cpu: P, memory: M, io: I) -> Self {
+        Self {
+            machine: Machine::new(cpu, memory),
+            io,
+            trace: NoTrace,
+            quiet: false,
+            inputs_read: 0,
         }
     }
 }
 
-// instructions definitions
-impl<P, M> Orchestrator<P, M>
+impl<P, M, I, T> Orchestrator<P, M, I, T>
 where
     P: ProcessorInterface,
     M: MemoryInterface,
+    I: IoDevice,
+    T: TraceSink,
 {
-    fn read(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(
-            debug,
-            &format!("READ from keyboard -> Memory[+{:0>4}]", address),
-        );
-
-        print!("Enter a number: ");
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-
-        let value: i32 = match input.trim().parse() {
-            Ok(v) => v,
-            Err(_) => {
-                eprintln!("Invalid number");
-                return Err(SimpletronError::InvalidReadInput(input));
-            }
-        };
-
-        self.memory.store_data(MemoryPayload {
-            address,
-            data: MemoryData {
-                value: value.to_string(),
-            },
-        })?;
-        self.cpu.increment_pc();
-        Ok(())
+    /// Sends trace events to `trace` instead.
+    pub fn with_trace<U: TraceSink>(self, trace: U) -> Orchestrator<P, M, I, U> {
+        Orchestrator {
+            machine: self.machine,
+            io: self.io,
+            trace,
+            quiet: self.quiet,
+            inputs_read: self.inputs_read,
+        }
     }
 
-    fn write(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        let value = self.memory.read_data(address)?;
-        self.debug(
-            debug,
-            &format!("WRITE <- Memory[+{:0>4}] = {}", address, value),
-        );
-        println!("Memory[{}] = {}", address, value);
-        self.cpu.increment_pc();
-        Ok(())
+    /// Leaves out the banner and the register and memory dump at `HALT`,
+    /// so only the program's own output is printed.
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
     }
 
-    fn write_acc(&mut self, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("Writing ACC value"));
-
-        let value = self.cpu.get_acc_value();
-        println!("ACC: {}", value);
-        self.cpu.increment_pc();
-        Ok(())
+    pub fn io(&self) -> &I {
+        &self.io
     }
 
-    fn read_i(&mut self, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("READ from keyboard -> ACC"));
-
-        print!("Enter a number: ");
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-
-        let value: i32 = match input.trim().parse() {
-            Ok(v) => v,
-            Err(_) => {
-                eprintln!("Invalid number");
-                return Err(SimpletronError::InvalidReadInput(input));
-            }
-        };
-        self.cpu.write_acc(value);
-        self.cpu.increment_pc();
-        Ok(())
+    pub fn io_mut(&mut self) -> &mut I {
+        &mut self.io
     }
 
-    fn load_m(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC <- Memory[+{:0>4}]", address));
-        let value = self.memory.read_data(address)?;
-        self.cpu.write_acc(value.parse().unwrap());
-        self.cpu.increment_pc();
-        Ok(())
+    pub fn set_debug_info(&mut self, debug_info: DebugInfo) {
+        self.machine.set_debug_info(debug_info);
     }
 
-    fn store(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC -> Memory[+{:0>4}]", address));
-
-        self.memory.store_data(MemoryPayload {
-            address,
-            data: MemoryData {
-                value: self.cpu.get_acc_value().to_string(),
-            },
-        })?;
-
-        self.cpu.increment_pc();
-        Ok(())
+    /// Starts execution at `entry` instead of address 0.
+    pub fn set_entry(&mut self, entry: usize) -> Result<(), SimpletronError> {
+        self.machine.set_pc(entry)
     }
 
-    fn load_i(&mut self, operand: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC <- {}", operand));
-
-        self.cpu.write_acc(operand.try_into().unwrap());
-        self.cpu.increment_pc();
-        Ok(())
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.machine.set_limits(limits);
     }
 
-    fn add_m(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC += value at Memory[+{:0>4}]", address));
-
-        let value: i32 = self
-            .memory
-            .read_data(address)?
-            .parse()
-            .map_err(|_| SimpletronError::InvalidMemoryData(address))?;
-
-        let acc = self.cpu.get_acc_value() as i32;
-        self.cpu.write_acc(acc + value);
-
-        self.cpu.increment_pc();
-        Ok(())
+    pub fn set_overflow(&mut self, policy: OverflowPolicy) {
+        self.machine.set_overflow(policy);
     }
 
-    fn sub_m(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC -= value at Memory[+{:0>4}]", address));
-
-        let value: i32 = self
-            .memory
-            .read_data(address)?
-            .parse()
-            .map_err(|_| SimpletronError::InvalidMemoryData(address))?;
-
-        let acc = self.cpu.get_acc_value() as i32;
-        self.cpu.write_acc(acc - value);
-
-        self.cpu.increment_pc();
-        Ok(())
+    /// Decodes memory with `isa` instead of the standard Simpletron set.
+    pub fn set_instruction_set(&mut self, isa: &'static dyn InstructionSet) {
+        self.machine.set_instruction_set(isa);
     }
 
-    fn mul_m(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC *= value at Memory[+{:0>4}]", address));
-
-        let value: i32 = self
-            .memory
-            .read_data(address)?
-            .parse()
-            .map_err(|_| SimpletronError::InvalidMemoryData(address))?;
-
-        let acc = self.cpu.get_acc_value() as i32;
-        self.cpu.write_acc(acc * value);
-
-        self.cpu.increment_pc();
-        Ok(())
+    pub fn machine(&self) -> &Machine<P, M> {
+        &self.machine
     }
 
-    fn div_m(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC /= value at Memory[+{:0>4}]", address));
-
-        let divisor: i32 = self
-            .memory
-            .read_data(address)?
-            .parse()
-            .map_err(|_| SimpletronError::InvalidMemoryData(address))?;
-
-        if divisor == 0 {
-            return Err(SimpletronError::DivisionByZero);
-        }
-
-        let acc = self.cpu.get_acc_value() as i32;
-        self.cpu.write_acc(acc / divisor);
-
-        self.cpu.increment_pc();
-        Ok(())
+    pub fn machine_mut(&mut self) -> &mut Machine<P, M> {
+        &mut self.machine
     }
 
-    fn mod_m(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC %= value at Memory[+{:0>4}]", address));
-
-        let divisor: i32 = self
-            .memory
-            .read_data(address)?
-            .parse()
-            .map_err(|_| SimpletronError::InvalidMemoryData(address))?;
-
-        if divisor == 0 {
-            return Err(SimpletronError::DivisionByZero);
-        }
-
-        let acc = self.cpu.get_acc_value() as i32;
-        self.cpu.write_acc((acc % divisor) as i32);
-
-        self.cpu.increment_pc();
-        Ok(())
-    }
-
-    fn add_i(&mut self, operand: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC += value {}", operand));
-
-        let acc = self.cpu.get_acc_value() as i32;
-        self.cpu.write_acc((acc + operand as i32) as i32);
-
-        self.cpu.increment_pc();
-        Ok(())
-    }
-
-    fn sub_i(&mut self, operand: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC -= value {}", operand));
-
-        let acc = self.cpu.get_acc_value() as i32;
-        self.cpu.write_acc((acc - operand as i32) as i32);
-
-        self.cpu.increment_pc();
-        Ok(())
-    }
-
-    fn mul_i(&mut self, operand: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC *= value {}", operand));
-
-        let acc = self.cpu.get_acc_value() as i32;
-        self.cpu.write_acc(acc * operand as i32);
-
-        self.cpu.increment_pc();
-        Ok(())
-    }
-
-    fn div_i(&mut self, operand: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC /= value {}", operand));
-
-        if operand == 0 {
-            return Err(SimpletronError::DivisionByZero);
-        }
-
-        let acc = self.cpu.get_acc_value() as i32;
-        self.cpu.write_acc(acc / operand as i32);
-
-        self.cpu.increment_pc();
-        Ok(())
-    }
-
-    fn mod_i(&mut self, operand: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("ACC %= value {}", operand));
-
-        if operand == 0 {
-            return Err(SimpletronError::DivisionByZero);
-        }
-
-        let acc = self.cpu.get_acc_value() as i32;
-        self.cpu.write_acc(acc % operand as i32);
-
-        self.cpu.increment_pc();
-        Ok(())
-    }
-
-    fn jump(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("JUMP -> address Memory[+{:0>4}]", address));
-        self.cpu.set_pc(address)?;
-        Ok(())
-    }
-
-    fn jump_if_negative(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(
-            debug,
-            &format!("JUMP IF NEG -> address Memory[+{:0>4}]", address),
-        );
-
-        let acc = self.cpu.get_acc_value() as i32;
-        if acc < 0 {
-            self.cpu.set_pc(address)?;
-        } else {
-            self.cpu.increment_pc();
-        }
-        Ok(())
+    pub fn trace(&self) -> &T {
+        &self.trace
     }
 
-    fn jump_if_zero(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(
-            debug,
-            &format!("JUMP IF ZERO -> address Memory[+{:0>4}]", address),
-        );
-
-        let acc = self.cpu.get_acc_value();
-        if acc == 0 {
-            self.cpu.set_pc(address)?;
-        } else {
-            self.cpu.increment_pc();
+    /// Runs the program to its `HALT` and returns the exit status.
+    pub fn run(&mut self) -> Result<u8, SimpletronError> {
+        if !self.quiet {
+            println!("*** Welcome to Simpletron ***");
+            println!("*** Program Loaded Succesfully ***\n");
         }
-        Ok(())
-    }
-
-    fn halt(&mut self, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(debug, &format!("HALT"));
-
-        self.cpu.dump();
-        self.memory.dump(-1);
-
-        std::process::exit(0);
-    }
-
-    fn jump_if_not_zero(&mut self, address: usize, debug: bool) -> Result<(), SimpletronError> {
-        self.debug(
-            debug,
-            &format!("JUMP IF NOT ZERO -> address Memory[+{:0>4}]", address),
-        );
-
-        let acc = self.cpu.get_acc_value();
+        loop {
+            let outcome = if self.trace.enabled(TraceLevel::Instructions) {
+                if let Some(instruction) = self.machine.current_instruction() {
+                    self.trace.event(&TraceEvent::Instruction {
+                        address: self.machine.pc(),
+                        instruction,
+                    });
+                }
+                if self.machine.needs_input() {
+                    self.read_input();
+                }
+                self.machine.step()
+            } else {
+                self.machine.run_for(BATCH_STEPS)
+            };
+            for output in self.machine.take_output() {
+                if self.trace.enabled(TraceLevel::Io) {
+                    self.trace.event(&TraceEvent::Output(output));
+                }
+                self.io.write(output)?;
+            }
 
-        if acc != 0 {
-            self.cpu.set_pc(address)?;
-        } else {
-            self.cpu.increment_pc();
+            match outcome {
+                StepOutcome::Continued => {
+                    if self.trace.enabled(TraceLevel::Instructions) {
+                        self.trace.event(&TraceEvent::Executed {
+                            cpu: self.machine.cpu(),
+                            memory: self.machine.memory(),
+                        });
+                    }
+                }
+                StepOutcome::AwaitingInput => self.read_input(),
+                StepOutcome::Halted => {
+                    let status = self.machine.exit_status().unwrap_or_default();
+                    if self.trace.enabled(TraceLevel::Faults) {
+                        self.trace.event(&TraceEvent::Halt(status));
+                    }
+                    if !self.quiet {
+                        self.machine.cpu().dump();
+                        self.machine.memory().dump(-1);
+                    }
+                    return Ok(status);
+                }
+                StepOutcome::Fault => {
+                    let fault = self.machine.take_fault().expect("the machine faulted");
+                    if self.trace.enabled(TraceLevel::Faults) {
+                        self.trace.event(&TraceEvent::Fault(&fault));
+                    }
+                    return Err(SimpletronError::Fault(Box::new(fault)));
+                }
+            }
         }
-        Ok(())
     }
 
-    fn jump_if_greater_than_zero(
-        &mut self,
-        address: usize,
-        debug: bool,
-    ) -> Result<(), SimpletronError> {
-        self.debug(
-            debug,
-            &format!(
-                "JUMP IF GREATER THAN ZERO -> address Memory[+{:0>4}]",
-                address
-            ),
-        );
-
-        let acc = self.cpu.get_acc_value();
-
-        if acc > 0 {
-            self.cpu.set_pc(address)?;
-        } else {
-            self.cpu.increment_pc();
+    /// Queues the next value from the device, or stops the machine with a
+    /// fault if there is none or it is not a number.
+    fn read_input(&mut self) {
+        match self.io.read() {
+            Ok(Some(value)) => {
+                if self.trace.enabled(TraceLevel::Io) {
+                    self.trace.event(&TraceEvent::Input(value));
+                }
+                self.inputs_read += 1;
+                self.machine.push_input(value);
+            }
+            Ok(None) => {
+                self.machine.fail(SimpletronError::EndOfInput {
+                    given: self.inputs_read,
+                });
+            }
+            Err(error) => {
+                self.machine.fail(error);
+            }
         }
-        Ok(())
     }
 }
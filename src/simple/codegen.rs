@@ -0,0 +1,362 @@
+use std::cmp::Ordering;
+
+use crate::{
+    assembler::instruction::Mnemonic,
+    simple::parser::{Command, Relation, Statement, Token},
+    vm::error::SimpletronError,
+};
+
+/// Largest value an immediate operand can hold.
+const MAX_IMMEDIATE: i32 = 99;
+
+/// Symbolic operand, resolved to an address by a backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ref {
+    Var(String),
+    Const(i32),
+    Temp(usize),
+    Immediate(usize),
+    Line(u32),
+    /// A jump target generated by the compiler, placed by [`Item::Label`].
+    Label(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Op {
+    pub mnemonic: Mnemonic,
+    pub operand: Option<Ref>,
+    /// 1-based source line the instruction was generated from.
+    pub source_line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum Item {
+    /// Start of the code for a Simple line number.
+    Line(u32),
+    Label(usize),
+    Op(Op),
+}
+
+/// Expression operand while evaluating postfix code.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Ref(Ref),
+    Acc,
+}
+
+struct Generator {
+    items: Vec<Item>,
+    free_temps: Vec<usize>,
+    temp_count: usize,
+    label_count: usize,
+    line: usize,
+}
+
+pub fn generate(statements: &[Statement]) -> Result<Vec<Item>, SimpletronError> {
+    let mut generator = Generator {
+        items: Vec::new(),
+        free_temps: Vec::new(),
+        temp_count: 0,
+        label_count: 0,
+        line: 0,
+    };
+
+    for statement in statements {
+        generator.line = statement.source_line;
+        generator.items.push(Item::Line(statement.number));
+        generator.statement(&statement.command)?;
+    }
+
+    if !statements
+        .iter()
+        .any(|statement| matches!(statement.command, Command::End))
+    {
+        return Err(SimpletronError::CompileError {
+            line: statements
+                .last()
+                .map_or(1, |statement| statement.source_line),
+            message: "program has no `end` statement".to_string(),
+        });
+    }
+
+    Ok(generator.items)
+}
+
+impl Generator {
+    fn emit(&mut self, mnemonic: Mnemonic, operand: Option<Ref>) {
+        self.items.push(Item::Op(Op {
+            mnemonic,
+            operand,
+            source_line: self.line,
+        }));
+    }
+
+    fn statement(&mut self, command: &Command) -> Result<(), SimpletronError> {
+        match command {
+            Command::Rem => {}
+            Command::Input(names) => {
+                for name in names {
+                    self.emit(Mnemonic::Read, Some(Ref::Var(name.clone())));
+                }
+            }
+            Command::Let(name, expr) => {
+                if let [Token::Variable(source)] = expr.as_slice() {
+                    self.emit(Mnemonic::LoadM, Some(Ref::Var(source.clone())));
+                } else {
+                    self.evaluate(expr)?;
+                }
+                self.emit(Mnemonic::Store, Some(Ref::Var(name.clone())));
+            }
+            Command::Print(expr) => match expr.as_slice() {
+                [Token::Variable(name)] => self.emit(Mnemonic::Write, Some(Ref::Var(name.clone()))),
+                _ => {
+                    self.evaluate(expr)?;
+                    self.emit(Mnemonic::WriteAcc, None);
+                }
+            },
+            Command::Goto(target) => self.emit(Mnemonic::Jump, Some(Ref::Line(*target))),
+            Command::If {
+                left,
+                relation,
+                right,
+                target,
+            } => {
+                let left = self.operand(left)?;
+                let right = self.operand(right)?;
+                self.compare(&left, *relation, &right, *target);
+                self.release(&left);
+                self.release(&right);
+            }
+            Command::End => self.emit(Mnemonic::Halt, None),
+        }
+        Ok(())
+    }
+
+    fn constant(&self, value: i32) -> Ref {
+        if (0..=MAX_IMMEDIATE).contains(&value) {
+            Ref::Immediate(value as usize)
+        } else {
+            Ref::Const(value)
+        }
+    }
+
+    fn label(&mut self) -> usize {
+        self.label_count += 1;
+        self.label_count - 1
+    }
+
+    fn place(&mut self, label: usize) {
+        self.items.push(Item::Label(label));
+    }
+
+    /// A comparison operand: a variable or constant as is, anything else
+    /// evaluated into a temporary.
+    fn operand(&mut self, postfix: &[Token]) -> Result<Value, SimpletronError> {
+        match postfix {
+            [Token::Number(value)] => Ok(Value::Ref(self.constant(*value))),
+            [Token::Variable(name)] => Ok(Value::Ref(Ref::Var(name.clone()))),
+            _ => {
+                self.evaluate(postfix)?;
+                let temp = self.temp();
+                self.emit(Mnemonic::Store, Some(Ref::Temp(temp)));
+                Ok(Value::Ref(Ref::Temp(temp)))
+            }
+        }
+    }
+
+    /// Jumps to `target` if `left relation right` holds. Operands of
+    /// opposite signs are ordered by their signs alone; `left - right` is
+    /// only computed when the signs match, where it cannot overflow.
+    fn compare(&mut self, left: &Value, relation: Relation, right: &Value, target: u32) {
+        let done = self.label();
+        let same_sign = self.label();
+        let outcome = |ordering| {
+            if holds(relation, ordering) {
+                Ref::Line(target)
+            } else {
+                Ref::Label(done)
+            }
+        };
+        let (less, greater) = (outcome(Ordering::Less), outcome(Ordering::Greater));
+
+        match (is_negative(left), is_negative(right)) {
+            (Some(left_negative), Some(right_negative)) if left_negative != right_negative => {
+                let ordered = if left_negative { less } else { greater };
+                self.emit(Mnemonic::Jump, Some(ordered));
+                self.place(done);
+                return;
+            }
+            (Some(_), Some(_)) => {}
+            (None, Some(false)) => {
+                self.load(left);
+                self.emit(Mnemonic::JumpIfNegative, Some(less));
+            }
+            (None, Some(true)) => {
+                self.load(left);
+                self.emit(Mnemonic::JumpIfNegative, Some(Ref::Label(same_sign)));
+                self.emit(Mnemonic::Jump, Some(greater));
+            }
+            (Some(false), None) => {
+                self.load(right);
+                self.emit(Mnemonic::JumpIfNegative, Some(greater));
+            }
+            (Some(true), None) => {
+                self.load(right);
+                self.emit(Mnemonic::JumpIfNegative, Some(Ref::Label(same_sign)));
+                self.emit(Mnemonic::Jump, Some(less));
+            }
+            (None, None) => {
+                let left_negative = self.label();
+                self.load(left);
+                self.emit(Mnemonic::JumpIfNegative, Some(Ref::Label(left_negative)));
+                self.load(right);
+                self.emit(Mnemonic::JumpIfNegative, Some(greater));
+                self.emit(Mnemonic::Jump, Some(Ref::Label(same_sign)));
+                self.place(left_negative);
+                self.load(right);
+                self.emit(Mnemonic::JumpIfNegative, Some(Ref::Label(same_sign)));
+                self.emit(Mnemonic::Jump, Some(less));
+            }
+        }
+
+        self.place(same_sign);
+        self.load(left);
+        match right {
+            Value::Ref(Ref::Immediate(n)) => self.emit(Mnemonic::SubI, Some(Ref::Immediate(*n))),
+            Value::Ref(other) => self.emit(Mnemonic::SubM, Some(other.clone())),
+            Value::Acc => unreachable!("comparison operands are never the accumulator"),
+        }
+        let jumps: &[Mnemonic] = match relation {
+            Relation::Equal => &[Mnemonic::JumpIfZero],
+            Relation::NotEqual => &[Mnemonic::JumpIfNotZero],
+            Relation::Less => &[Mnemonic::JumpIfNegative],
+            Relation::Greater => &[Mnemonic::JumpIfGreaterThanZero],
+            Relation::LessEqual => &[Mnemonic::JumpIfNegative, Mnemonic::JumpIfZero],
+            Relation::GreaterEqual => &[Mnemonic::JumpIfGreaterThanZero, Mnemonic::JumpIfZero],
+        };
+        for jump in jumps {
+            self.emit(*jump, Some(Ref::Line(target)));
+        }
+        self.place(done);
+    }
+
+    fn temp(&mut self) -> usize {
+        self.free_temps.pop().unwrap_or_else(|| {
+            self.temp_count += 1;
+            self.temp_count - 1
+        })
+    }
+
+    fn release(&mut self, value: &Value) {
+        if let Value::Ref(Ref::Temp(index)) = value {
+            self.free_temps.push(*index);
+        }
+    }
+
+    fn load(&mut self, value: &Value) {
+        match value {
+            Value::Acc => {}
+            Value::Ref(Ref::Immediate(n)) => self.emit(Mnemonic::LoadI, Some(Ref::Immediate(*n))),
+            Value::Ref(other) => self.emit(Mnemonic::LoadM, Some(other.clone())),
+        }
+    }
+
+    /// Evaluates a postfix expression, leaving the result in the accumulator.
+    fn evaluate(&mut self, postfix: &[Token]) -> Result<(), SimpletronError> {
+        let mut stack: Vec<Value> = Vec::new();
+
+        for token in postfix {
+            match token {
+                Token::Number(value) => stack.push(Value::Ref(self.constant(*value))),
+                Token::Variable(name) => stack.push(Value::Ref(Ref::Var(name.clone()))),
+                Token::Operator(op) => {
+                    let (Some(right), Some(left)) = (stack.pop(), stack.pop()) else {
+                        return Err(SimpletronError::CompileError {
+                            line: self.line,
+                            message: "malformed expression".to_string(),
+                        });
+                    };
+
+                    let right = self.spill_if_acc(right, &left, &mut stack);
+
+                    self.load(&left);
+                    let (memory, immediate) = arithmetic(*op);
+                    match &right {
+                        Value::Ref(Ref::Immediate(n)) => {
+                            self.emit(immediate, Some(Ref::Immediate(*n)))
+                        }
+                        Value::Ref(other) => self.emit(memory, Some(other.clone())),
+                        Value::Acc => unreachable!("spilled above"),
+                    }
+
+                    self.release(&left);
+                    self.release(&right);
+                    stack.push(Value::Acc);
+                }
+            }
+        }
+
+        match stack.as_slice() {
+            [value] => {
+                let value = value.clone();
+                self.load(&value);
+                self.release(&value);
+                Ok(())
+            }
+            _ => Err(SimpletronError::CompileError {
+                line: self.line,
+                message: "malformed expression".to_string(),
+            }),
+        }
+    }
+
+    /// Stores any accumulator value that the next operation would clobber:
+    /// entries further down the stack, or a right operand when the left one
+    /// still has to be loaded.
+    fn spill_if_acc(&mut self, right: Value, left: &Value, stack: &mut [Value]) -> Value {
+        let mut right = right;
+
+        if *left != Value::Acc {
+            for value in stack.iter_mut().chain(std::iter::once(&mut right)) {
+                if *value == Value::Acc {
+                    let temp = self.temp();
+                    self.emit(Mnemonic::Store, Some(Ref::Temp(temp)));
+                    *value = Value::Ref(Ref::Temp(temp));
+                }
+            }
+        }
+
+        right
+    }
+}
+
+/// Whether `relation` holds between operands ordered as `ordering`.
+fn holds(relation: Relation, ordering: Ordering) -> bool {
+    match relation {
+        Relation::Equal => ordering.is_eq(),
+        Relation::NotEqual => ordering.is_ne(),
+        Relation::Less => ordering.is_lt(),
+        Relation::Greater => ordering.is_gt(),
+        Relation::LessEqual => ordering.is_le(),
+        Relation::GreaterEqual => ordering.is_ge(),
+    }
+}
+
+/// The sign of an operand, if it is known at compile time.
+fn is_negative(value: &Value) -> Option<bool> {
+    match value {
+        Value::Ref(Ref::Immediate(_)) => Some(false),
+        Value::Ref(Ref::Const(value)) => Some(*value < 0),
+        _ => None,
+    }
+}
+
+fn arithmetic(op: char) -> (Mnemonic, Mnemonic) {
+    match op {
+        '+' => (Mnemonic::AddM, Mnemonic::AddI),
+        '-' => (Mnemonic::SubM, Mnemonic::SubI),
+        '*' => (Mnemonic::MulM, Mnemonic::MulI),
+        '/' => (Mnemonic::DivM, Mnemonic::DivI),
+        _ => (Mnemonic::ModM, Mnemonic::ModI),
+    }
+}
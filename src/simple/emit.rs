@@ -0,0 +1,289 @@
+use std::fmt::Write;
+
+use crate::{
+    assembler::{
+        VarTable, encoder,
+        instruction::{AsmInstruction, Mnemonic, Operand},
+    },
+    simple::codegen::{Item, Op, Ref},
+    vm::{debug_info::DebugInfo, error::SimpletronError},
+};
+
+/// Largest value a memory word can hold.
+const MAX_WORD: i32 = 9999;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Symbol {
+    Line(u32),
+    Label(usize),
+    Var(String),
+    Const(i32),
+    Temp(usize),
+}
+
+struct TableEntry {
+    symbol: Symbol,
+    location: usize,
+}
+
+/// Classic Simple compiler symbol table: line numbers map to code addresses,
+/// variables, constants and temporaries to data cells allocated downwards
+/// from the top of memory.
+struct SymbolTable {
+    entries: Vec<TableEntry>,
+    memory_size: usize,
+    data_count: usize,
+}
+
+impl SymbolTable {
+    fn lookup(&self, symbol: &Symbol) -> Option<usize> {
+        self.entries
+            .iter()
+            .find(|entry| entry.symbol == *symbol)
+            .map(|entry| entry.location)
+    }
+
+    fn data(&mut self, symbol: Symbol, code_end: usize) -> Result<usize, SimpletronError> {
+        if let Some(location) = self.lookup(&symbol) {
+            return Ok(location);
+        }
+
+        self.data_count += 1;
+        if code_end + self.data_count > self.memory_size {
+            return Err(SimpletronError::ProgramTooLarge {
+                size: code_end + self.data_count,
+                capacity: self.memory_size,
+            });
+        }
+
+        let location = self.memory_size - self.data_count;
+        self.entries.push(TableEntry { symbol, location });
+        Ok(location)
+    }
+}
+
+/// Lays out the program directly as a memory image. Jumps to line numbers
+/// or labels that have not been seen yet are emitted with a zero operand and flagged;
+/// a second pass patches them once every line's address is known. Also
+/// returns the address of each variable.
+pub fn words(
+    items: &[Item],
+    memory_size: usize,
+    source: &str,
+) -> Result<(Vec<u16>, DebugInfo, VarTable), SimpletronError> {
+    let source_lines: Vec<&str> = source.lines().collect();
+    let mut image = vec![0u16; memory_size];
+    let mut flags: Vec<Option<(Symbol, usize)>> = vec![None; memory_size];
+    let mut table = SymbolTable {
+        entries: Vec::new(),
+        memory_size,
+        data_count: 0,
+    };
+    let mut debug_info = DebugInfo::new();
+    let mut pc = 0;
+
+    for item in items {
+        let op = match item {
+            Item::Line(number) => {
+                table.entries.push(TableEntry {
+                    symbol: Symbol::Line(*number),
+                    location: pc,
+                });
+                continue;
+            }
+            Item::Label(label) => {
+                table.entries.push(TableEntry {
+                    symbol: Symbol::Label(*label),
+                    location: pc,
+                });
+                continue;
+            }
+            Item::Op(op) => op,
+        };
+
+        if pc + 1 + table.data_count > memory_size {
+            return Err(SimpletronError::ProgramTooLarge {
+                size: pc + 1 + table.data_count,
+                capacity: memory_size,
+            });
+        }
+
+        let address = match &op.operand {
+            None => 0,
+            Some(Ref::Immediate(value)) => *value,
+            Some(Ref::Line(number)) => jump_target(
+                &table,
+                &mut flags[pc],
+                Symbol::Line(*number),
+                op.source_line,
+            ),
+            Some(Ref::Label(label)) => jump_target(
+                &table,
+                &mut flags[pc],
+                Symbol::Label(*label),
+                op.source_line,
+            ),
+            Some(Ref::Var(name)) => table.data(Symbol::Var(name.clone()), pc + 1)?,
+            Some(Ref::Temp(index)) => table.data(Symbol::Temp(*index), pc + 1)?,
+            Some(Ref::Const(value)) => {
+                if !(0..=MAX_WORD).contains(value) {
+                    return Err(SimpletronError::CompileError {
+                        line: op.source_line,
+                        message: format!("constant {} does not fit in a memory word", value),
+                    });
+                }
+                let location = table.data(Symbol::Const(*value), pc + 1)?;
+                image[location] = *value as u16;
+                location
+            }
+        };
+
+        image[pc] = encode(op.mnemonic, address)?;
+        if let Some(text) = source_lines.get(op.source_line - 1) {
+            debug_info.insert(pc, op.source_line, text);
+        }
+        pc += 1;
+    }
+
+    // Second pass: resolve forward references recorded in the flags.
+    for (address, flag) in flags.iter().enumerate() {
+        if let Some((symbol, line)) = flag {
+            let location = table.lookup(symbol).ok_or_else(|| {
+                let message = match symbol {
+                    Symbol::Line(number) => format!("goto to undefined line {}", number),
+                    _ => "jump to an undefined label".to_string(),
+                };
+                SimpletronError::CompileError {
+                    line: *line,
+                    message,
+                }
+            })?;
+            image[address] += location as u16;
+        }
+    }
+
+    let vars = table
+        .entries
+        .into_iter()
+        .filter_map(|entry| match entry.symbol {
+            Symbol::Var(name) => Some((name, entry.location)),
+            _ => None,
+        })
+        .collect();
+    Ok((image, debug_info, vars))
+}
+
+/// The address of a jump target, or 0 with the instruction flagged for the
+/// second pass if the target comes later.
+fn jump_target(
+    table: &SymbolTable,
+    flag: &mut Option<(Symbol, usize)>,
+    symbol: Symbol,
+    source_line: usize,
+) -> usize {
+    table.lookup(&symbol).unwrap_or_else(|| {
+        *flag = Some((symbol, source_line));
+        0
+    })
+}
+
+fn encode(mnemonic: Mnemonic, address: usize) -> Result<u16, SimpletronError> {
+    encoder::encode(&AsmInstruction {
+        mnemonic,
+        operand: Some(Operand::Immediate(address)),
+    })
+}
+
+fn operand_name(operand: &Ref) -> String {
+    match operand {
+        Ref::Var(name) => name.clone(),
+        Ref::Const(value) => format!("_c{}", value),
+        Ref::Temp(index) => format!("_t{}", index),
+        Ref::Immediate(value) => value.to_string(),
+        Ref::Line(number) => format!("_line{}", number),
+        Ref::Label(label) => format!("_label{}", label),
+    }
+}
+
+/// Renders the program as mnemonic source for the assembler. Constants
+/// that do not fit an immediate are built in a prologue.
+pub fn source(items: &[Item]) -> Result<String, SimpletronError> {
+    let ops: Vec<&Op> = items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Op(op) => Some(op),
+            Item::Line(_) | Item::Label(_) => None,
+        })
+        .collect();
+
+    let mut cells: Vec<&Ref> = Vec::new();
+    for op in &ops {
+        if let Some(operand @ (Ref::Var(_) | Ref::Const(_) | Ref::Temp(_))) = &op.operand
+            && !cells.contains(&operand)
+        {
+            cells.push(operand);
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "; generated by the Simple compiler").unwrap();
+    for cell in &cells {
+        writeln!(out, "VAR {}", operand_name(cell)).unwrap();
+    }
+    writeln!(out).unwrap();
+
+    for cell in &cells {
+        let Ref::Const(value) = cell else {
+            continue;
+        };
+        if !(0..=MAX_WORD).contains(value) {
+            let line = ops
+                .iter()
+                .find(|op| op.operand.as_ref() == Some(*cell))
+                .map_or(0, |op| op.source_line);
+            return Err(SimpletronError::CompileError {
+                line,
+                message: format!("constant {} does not fit in a memory word", value),
+            });
+        }
+
+        let digits = value.to_string();
+        for (index, digit) in digits.chars().enumerate() {
+            if index == 0 {
+                writeln!(out, "LOADI {}", digit).unwrap();
+            } else {
+                writeln!(out, "MULI 10").unwrap();
+                writeln!(out, "ADDI {}", digit).unwrap();
+            }
+        }
+        writeln!(out, "STORE {}", operand_name(cell)).unwrap();
+    }
+
+    let targets: Vec<&Ref> = ops.iter().filter_map(|op| op.operand.as_ref()).collect();
+
+    for item in items {
+        match item {
+            Item::Line(number) if targets.contains(&&Ref::Line(*number)) => {
+                writeln!(out, "_line{}:", number).unwrap()
+            }
+            Item::Label(label) if targets.contains(&&Ref::Label(*label)) => {
+                writeln!(out, "_label{}:", label).unwrap()
+            }
+            Item::Line(_) | Item::Label(_) => {}
+            Item::Op(op) => {
+                match &op.operand {
+                    Some(operand) => writeln!(
+                        out,
+                        "    {:<6} {}",
+                        op.mnemonic.mnemonic(),
+                        operand_name(operand)
+                    ),
+                    None => writeln!(out, "    {}", op.mnemonic.mnemonic()),
+                }
+                .unwrap();
+            }
+        }
+    }
+
+    Ok(out)
+}
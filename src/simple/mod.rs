@@ -0,0 +1,61 @@
+//! Compiler for the textbook Simple language (`rem`, `input`, `let`,
+//! `print`, `goto`, `if ... goto`, `end`).
+
+use crate::{
+    assembler::VarTable,
+    simple::codegen::{Item, Ref},
+    vm::{debug_info::DebugInfo, error::SimpletronError},
+};
+
+pub mod codegen;
+pub mod emit;
+pub mod parser;
+
+/// Memory size the word backend lays programs out for.
+pub const MEMORY_SIZE: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct CompiledProgram {
+    /// Full memory image: code from address 0, data from the top down.
+    pub words: Vec<u16>,
+    pub debug_info: DebugInfo,
+    /// Data cell of each variable.
+    pub vars: VarTable,
+}
+
+/// Compiles Simple source straight to a memory image.
+pub fn compile(source: &str) -> Result<CompiledProgram, SimpletronError> {
+    let items = front_end(source)?;
+    let (words, debug_info, vars) = emit::words(&items, MEMORY_SIZE, source)?;
+    Ok(CompiledProgram {
+        words,
+        debug_info,
+        vars,
+    })
+}
+
+/// Compiles Simple source to mnemonic source for [`crate::assembler::assemble`].
+pub fn compile_to_source(source: &str) -> Result<String, SimpletronError> {
+    emit::source(&front_end(source)?)
+}
+
+fn front_end(source: &str) -> Result<Vec<Item>, SimpletronError> {
+    let statements = parser::parse(source)?;
+    let items = codegen::generate(&statements)?;
+
+    for item in &items {
+        if let Item::Op(op) = item
+            && let Some(Ref::Line(target)) = op.operand
+            && !statements
+                .iter()
+                .any(|statement| statement.number == target)
+        {
+            return Err(SimpletronError::CompileError {
+                line: op.source_line,
+                message: format!("goto to undefined line {}", target),
+            });
+        }
+    }
+
+    Ok(items)
+}
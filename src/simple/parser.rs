@@ -0,0 +1,265 @@
+use crate::vm::error::SimpletronError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+}
+
+/// One element of an expression in postfix order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Number(i32),
+    Variable(String),
+    Operator(char),
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Rem,
+    Input(Vec<String>),
+    Let(String, Vec<Token>),
+    Print(Vec<Token>),
+    Goto(u32),
+    If {
+        left: Vec<Token>,
+        relation: Relation,
+        right: Vec<Token>,
+        target: u32,
+    },
+    End,
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    /// Simple line number (the label at the start of the line).
+    pub number: u32,
+    /// 1-based line in the source text.
+    pub source_line: usize,
+    pub command: Command,
+}
+
+fn error(line: usize, message: impl Into<String>) -> SimpletronError {
+    SimpletronError::CompileError {
+        line,
+        message: message.into(),
+    }
+}
+
+pub fn parse(source: &str) -> Result<Vec<Statement>, SimpletronError> {
+    let mut statements: Vec<Statement> = Vec::new();
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let (number, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let number: u32 = number
+            .parse()
+            .map_err(|_| error(line, format!("expected a line number, found `{}`", number)))?;
+
+        if let Some(previous) = statements.last()
+            && previous.number >= number
+        {
+            return Err(error(
+                line,
+                format!(
+                    "line number {} is not greater than {}",
+                    number, previous.number
+                ),
+            ));
+        }
+
+        statements.push(Statement {
+            number,
+            source_line: line,
+            command: parse_command(rest.trim(), line)?,
+        });
+    }
+
+    Ok(statements)
+}
+
+fn parse_command(text: &str, line: usize) -> Result<Command, SimpletronError> {
+    let (keyword, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let rest = rest.trim();
+
+    match keyword.to_ascii_lowercase().as_str() {
+        "rem" => Ok(Command::Rem),
+        "input" => {
+            let names = rest
+                .split(',')
+                .map(|name| identifier(name.trim(), line))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Command::Input(names))
+        }
+        "let" => {
+            let (name, expr) = rest
+                .split_once('=')
+                .ok_or_else(|| error(line, "expected `let <variable> = <expression>`"))?;
+            Ok(Command::Let(
+                identifier(name.trim(), line)?,
+                to_postfix(expr, line)?,
+            ))
+        }
+        "print" => Ok(Command::Print(to_postfix(rest, line)?)),
+        "goto" => Ok(Command::Goto(target(rest, line)?)),
+        "if" => parse_if(rest, line),
+        "end" if rest.is_empty() => Ok(Command::End),
+        "end" => Err(error(line, "`end` takes no arguments")),
+        "" => Err(error(line, "missing command")),
+        other => Err(error(line, format!("unknown command `{}`", other))),
+    }
+}
+
+fn parse_if(text: &str, line: usize) -> Result<Command, SimpletronError> {
+    let lower = text.to_ascii_lowercase();
+    let goto = lower
+        .rfind("goto")
+        .ok_or_else(|| error(line, "expected `if <condition> goto <line>`"))?;
+    let condition = &text[..goto];
+    let target = target(text[goto + 4..].trim(), line)?;
+
+    const RELATIONS: [(&str, Relation); 6] = [
+        ("==", Relation::Equal),
+        ("!=", Relation::NotEqual),
+        ("<=", Relation::LessEqual),
+        (">=", Relation::GreaterEqual),
+        ("<", Relation::Less),
+        (">", Relation::Greater),
+    ];
+
+    let (index, op, relation) = RELATIONS
+        .iter()
+        .find_map(|(op, relation)| condition.find(op).map(|index| (index, *op, *relation)))
+        .ok_or_else(|| error(line, "expected a relational operator (== != < > <= >=)"))?;
+
+    Ok(Command::If {
+        left: to_postfix(&condition[..index], line)?,
+        relation,
+        right: to_postfix(&condition[index + op.len()..], line)?,
+        target,
+    })
+}
+
+fn target(text: &str, line: usize) -> Result<u32, SimpletronError> {
+    text.trim()
+        .parse()
+        .map_err(|_| error(line, format!("`{}` is not a line number", text.trim())))
+}
+
+fn identifier(name: &str, line: usize) -> Result<String, SimpletronError> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if valid {
+        Ok(name.to_ascii_lowercase())
+    } else {
+        Err(error(
+            line,
+            format!("`{}` is not a valid variable name", name),
+        ))
+    }
+}
+
+fn precedence(op: char) -> u8 {
+    match op {
+        '*' | '/' | '%' => 2,
+        _ => 1,
+    }
+}
+
+/// Converts an infix expression to postfix with the shunting-yard
+/// algorithm. Unary minus is rewritten as `0 x -`.
+pub fn to_postfix(expr: &str, line: usize) -> Result<Vec<Token>, SimpletronError> {
+    let mut output = Vec::new();
+    let mut operators: Vec<char> = Vec::new();
+    let mut expect_operand = true;
+    let mut chars = expr.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut digits = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(d);
+                chars.next();
+            }
+            let value = digits
+                .parse()
+                .map_err(|_| error(line, format!("constant {} is too large", digits)))?;
+            output.push(Token::Number(value));
+            expect_operand = false;
+        } else if c.is_ascii_alphabetic() {
+            let mut name = String::new();
+            while let Some(&d) = chars
+                .peek()
+                .filter(|d| d.is_ascii_alphanumeric() || **d == '_')
+            {
+                name.push(d);
+                chars.next();
+            }
+            output.push(Token::Variable(identifier(&name, line)?));
+            expect_operand = false;
+        } else if c == '(' {
+            operators.push(c);
+            chars.next();
+            expect_operand = true;
+        } else if c == ')' {
+            chars.next();
+            loop {
+                match operators.pop() {
+                    Some('(') => break,
+                    Some(op) => output.push(Token::Operator(unary_as_minus(op))),
+                    None => return Err(error(line, "unbalanced `)`")),
+                }
+            }
+            expect_operand = false;
+        } else if c == '-' && expect_operand {
+            chars.next();
+            // Unary minus binds tighter than any binary operator.
+            output.push(Token::Number(0));
+            operators.push('~');
+        } else if "+-*/%".contains(c) {
+            chars.next();
+            if expect_operand {
+                return Err(error(line, format!("missing operand before `{}`", c)));
+            }
+            while let Some(&top) = operators.last() {
+                if top == '(' || (top != '~' && precedence(top) < precedence(c)) {
+                    break;
+                }
+                output.push(Token::Operator(unary_as_minus(operators.pop().unwrap())));
+            }
+            operators.push(c);
+            expect_operand = true;
+        } else {
+            return Err(error(line, format!("unexpected character `{}`", c)));
+        }
+    }
+
+    if expect_operand {
+        return Err(error(line, "incomplete expression"));
+    }
+    while let Some(op) = operators.pop() {
+        if op == '(' {
+            return Err(error(line, "unbalanced `(`"));
+        }
+        output.push(Token::Operator(unary_as_minus(op)));
+    }
+
+    Ok(output)
+}
+
+fn unary_as_minus(op: char) -> char {
+    if op == '~' { '-' } else { op }
+}
@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct SourceLine {
+    pub number: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DebugInfo {
+    lines: HashMap<usize, SourceLine>,
+}
+
+impl DebugInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, address: usize, number: usize, text: &str) {
+        self.lines.insert(
+            address,
+            SourceLine {
+                number,
+                text: text.trim().to_string(),
+            },
+        );
+    }
+
+    pub fn lookup(&self, address: usize) -> Option<&SourceLine> {
+        self.lines.get(&address)
+    }
+}
@@ -0,0 +1,106 @@
+use crate::vm::error::SimpletronError;
+use crate::vm::instruction::Instruction;
+use crate::vm::isa::{InstructionSet, OpcodeSpec};
+use crate::vm::loader::ParsedInstruction;
+use crate::vm::memory::MemoryInterface;
+use crate::vm::operation::Opcode;
+use crate::vm::word::Word;
+
+/// A decoded memory cell: the word, its instruction and table entry, and
+/// whether it starts a common pair that can run as one superinstruction
+/// (`LOADM` followed by `ADDM`, `SUBM`, `MULM`, `ADDI`, `SUBI`, `MULI` or
+/// `JZ`).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Decoded {
+    pub word: Word,
+    pub instruction: Instruction,
+    pub spec: &'static OpcodeSpec,
+    pub fuses: bool,
+}
+
+impl Decoded {
+    pub fn parsed(&self, address: usize) -> ParsedInstruction {
+        ParsedInstruction {
+            address,
+            data: self.word,
+        }
+    }
+}
+
+/// Decoded instructions by address, so a loop decodes each cell once.
+///
+/// A store to a cell drops its entry and the one before it, which may
+/// have fused with it, so self-modifying code sees its own writes. Words
+/// that do not decode are never cached; they fault every time they run.
+#[derive(Debug, Default)]
+pub(crate) struct DecodeCache {
+    entries: Vec<Option<Decoded>>,
+}
+
+impl DecodeCache {
+    pub fn get<M: MemoryInterface>(
+        &mut self,
+        isa: &'static dyn InstructionSet,
+        memory: &M,
+        address: usize,
+    ) -> Result<Decoded, SimpletronError> {
+        if let Some(Some(decoded)) = self.entries.get(address) {
+            return Ok(*decoded);
+        }
+
+        let word = memory.read_data(address)?;
+        let spec = isa.lookup(word)?;
+        let instruction = Instruction {
+            opcode: spec.operation,
+            operand: word.operand(),
+        };
+        let fuses = memory
+            .read_data(address + 1)
+            .is_ok_and(|next| fuses(isa, spec, next));
+        let decoded = Decoded {
+            word,
+            instruction,
+            spec,
+            fuses,
+        };
+
+        if self.entries.len() <= address {
+            self.entries
+                .resize(memory.get_memory_length().max(address + 1), None);
+        }
+        self.entries[address] = Some(decoded);
+        Ok(decoded)
+    }
+
+    /// Forgets the cell at `address` and any pair ending there.
+    pub fn invalidate(&mut self, address: usize) {
+        for cell in [address.checked_sub(1), Some(address)]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(entry) = self.entries.get_mut(cell) {
+                *entry = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+fn fuses(isa: &dyn InstructionSet, first: &OpcodeSpec, next: Word) -> bool {
+    first.operation == Opcode::LoadM
+        && isa.by_code(next.opcode()).is_some_and(|next| {
+            matches!(
+                next.operation,
+                Opcode::AddM
+                    | Opcode::SubM
+                    | Opcode::MulM
+                    | Opcode::AddI
+                    | Opcode::SubI
+                    | Opcode::MulI
+                    | Opcode::JumpIfZero
+            )
+        })
+}
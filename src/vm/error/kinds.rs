@@ -1,5 +1,10 @@
 use core::fmt;
-use std::{io, num::ParseIntError};
+use std::{io, num::ParseIntError, time::Duration};
+
+use crate::{
+    assembler::lint::LintWarning,
+    vm::{fault::FaultReport, operation::Opcode},
+};
 
 #[derive(Debug)]
 pub enum SimpletronError {
@@ -7,11 +12,28 @@ pub enum SimpletronError {
     InvalidAddressError(String),
     InvalidInstructionLine,
     Io(io::Error),
-    InvalidAddress { line: usize },
+    InvalidAddress {
+        line: usize,
+    },
     InvalidOpcode(i32),
     InvalidReadInput(String),
+    EndOfInput {
+        given: usize,
+    },
     DivisionByZero,
-    InvalidMemoryData(usize),
+    Overflow {
+        value: i64,
+    },
+    InstructionLimit {
+        limit: u64,
+    },
+    Timeout {
+        limit: Duration,
+    },
+    InfiniteLoop {
+        start: usize,
+        end: usize,
+    },
     InvalidInstruction(String),
     InvalidOperand(String),
     DuplicateLabel(String),
@@ -22,6 +44,44 @@ pub enum SimpletronError {
     UnresolvedVariable,
     ParseIntError(ParseIntError),
     MissingHalt,
+    Fault(Box<FaultReport>),
+    UnknownLint(String),
+    UnknownOverflowPolicy(String),
+    UnknownTraceLevel(String),
+    UnknownInstructionSet(String),
+    /// The instruction set has no opcode for this operation.
+    UnsupportedOperation {
+        operation: Opcode,
+        instruction_set: &'static str,
+    },
+    DeniedLints(Vec<LintWarning>),
+    OptimizationMismatch {
+        inputs: Vec<i32>,
+        detail: String,
+    },
+    UnsupportedFragment(String),
+    CompileError {
+        line: usize,
+        message: String,
+    },
+    ProgramTooLarge {
+        size: usize,
+        capacity: usize,
+    },
+    UnsupportedFrontEnd(String),
+    InvalidPseudoOp {
+        line: usize,
+        message: String,
+    },
+    Overlap {
+        address: usize,
+        first: String,
+        second: String,
+    },
+    InvalidSetting {
+        setting: String,
+        message: String,
+    },
 }
 
 impl fmt::Display for SimpletronError {
@@ -41,9 +101,29 @@ impl fmt::Display for SimpletronError {
             SimpletronError::InvalidOpcode(code) => write!(f, "{} is an invalid opcode", code),
             SimpletronError::InvalidReadInput(err) => write!(f, "{} is an invalid input", err),
             SimpletronError::DivisionByZero => write!(f, "division by zero error"),
-            SimpletronError::InvalidMemoryData(address) => {
-                write!(f, "invalid memory data at address: {}", address)
+            SimpletronError::EndOfInput { given: 0 } => {
+                write!(f, "READ at end of input: no values were given")
+            }
+            SimpletronError::EndOfInput { given } => write!(
+                f,
+                "READ at end of input: only {} value{} given",
+                given,
+                if *given == 1 { " was" } else { "s were" }
+            ),
+            SimpletronError::Overflow { value } => {
+                write!(f, "arithmetic overflow: {} does not fit in a word", value)
+            }
+            SimpletronError::InstructionLimit { limit } => {
+                write!(f, "instruction limit of {} reached", limit)
+            }
+            SimpletronError::Timeout { limit } => {
+                write!(f, "time limit of {}s reached", limit.as_secs_f64())
             }
+            SimpletronError::InfiniteLoop { start, end } => write!(
+                f,
+                "infinite loop between addresses {:02} and {:02}: the machine state repeated",
+                start, end
+            ),
             SimpletronError::InvalidInstruction(error) => {
                 write!(f, "{} is an invalid instruction", error)
             }
@@ -66,6 +146,105 @@ impl fmt::Display for SimpletronError {
                 write!(f, "ParseIntError {}", parse_int_error)
             }
             SimpletronError::MissingHalt => write!(f, "Missing Halt Command"),
+            SimpletronError::Fault(report) => write!(f, "{}", report),
+            SimpletronError::UnknownLint(name) => write!(f, "Unknown lint {}", name),
+            SimpletronError::UnknownTraceLevel(name) => write!(
+                f,
+                "Unknown trace level {} (expected off, faults, io or instructions)",
+                name
+            ),
+            SimpletronError::UnknownInstructionSet(name) => write!(
+                f,
+                "Unknown instruction set {} (expected simpletron or sml)",
+                name
+            ),
+            SimpletronError::UnsupportedOperation {
+                operation,
+                instruction_set,
+            } => write!(
+                f,
+                "{} is not in the {} instruction set",
+                operation.mnemonic(),
+                instruction_set
+            ),
+            SimpletronError::UnknownOverflowPolicy(name) => write!(
+                f,
+                "Unknown overflow policy {} (expected trap, wrap, saturate or unbounded)",
+                name
+            ),
+            SimpletronError::DeniedLints(warnings) => {
+                write!(f, "denied lints")?;
+                for warning in warnings {
+                    write!(f, "\n  {}", warning)?;
+                }
+                Ok(())
+            }
+            SimpletronError::OptimizationMismatch { inputs, detail } => {
+                write!(
+                    f,
+                    "optimized program differs on inputs {:?}: {}",
+                    inputs, detail
+                )
+            }
+            SimpletronError::CompileError { line, message } => {
+                write!(f, "compile error at line {}: {}", line, message)
+            }
+            SimpletronError::ProgramTooLarge { size, capacity } => write!(
+                f,
+                "program needs {} memory cells but only {} are available",
+                size, capacity
+            ),
+            SimpletronError::InvalidPseudoOp { line, message } => {
+                write!(f, "line {}: {}", line, message)
+            }
+            SimpletronError::Overlap {
+                address,
+                first,
+                second,
+            } => write!(
+                f,
+                "address {:02} is used by both {} and {}",
+                address, first, second
+            ),
+            SimpletronError::InvalidSetting { setting, message } => {
+                write!(f, "--set {}: {}", setting, message)
+            }
+            SimpletronError::UnsupportedFrontEnd(file) => {
+                write!(f, "--emit-asm needs a compiled source file, not {}", file)
+            }
+            SimpletronError::UnsupportedFragment(line) => {
+                write!(f, "{} is not supported in a straight-line fragment", line)
+            }
+        }
+    }
+}
+
+/// Process exit code for errors raised before the program runs: bad
+/// arguments, unreadable files and assembly errors.
+pub const SETUP_EXIT_CODE: u8 = 120;
+
+impl SimpletronError {
+    /// Process exit code for this error. Runtime faults get a code from
+    /// 102 to 111, one per kind, so they never collide with a `HALT` status
+    /// (0-99) or with 101, which Rust uses when the process panics. Errors
+    /// raised before the program runs exit with [`SETUP_EXIT_CODE`].
+    pub fn exit_code(&self) -> u8 {
+        let SimpletronError::Fault(report) = self else {
+            return SETUP_EXIT_CODE;
+        };
+        match report.error {
+            SimpletronError::DivisionByZero => 103,
+            SimpletronError::InvalidOpcode(_)
+            | SimpletronError::InvalidInstruction(_)
+            | SimpletronError::ParseIntError(_) => 104,
+            SimpletronError::InvalidAddressError(_) | SimpletronError::InvalidAddress { .. } => 105,
+            SimpletronError::InvalidReadInput(_) => 106,
+            SimpletronError::EndOfInput { .. } => 107,
+            SimpletronError::InstructionLimit { .. } => 108,
+            SimpletronError::Timeout { .. } => 109,
+            SimpletronError::InfiniteLoop { .. } => 110,
+            SimpletronError::Overflow { .. } => 111,
+            _ => 102,
         }
     }
 }
@@ -1,3 +1,3 @@
 mod kinds;
 
-pub use kinds::SimpletronError;
+pub use kinds::{SETUP_EXIT_CODE, SimpletronError};
@@ -0,0 +1,49 @@
+use std::fmt;
+
+use crate::vm::{
+    debug_info::SourceLine, error::SimpletronError, instruction::Instruction, processor::Registers,
+    word::Word,
+};
+
+/// Number of program counter values kept for fault reports.
+pub const PC_HISTORY_LEN: usize = 8;
+
+/// Machine context captured when an instruction fails at runtime.
+#[derive(Debug)]
+pub struct FaultReport {
+    pub error: SimpletronError,
+    pub address: usize,
+    pub word: Option<Word>,
+    pub instruction: Option<Instruction>,
+    pub registers: Registers,
+    pub history: Vec<usize>,
+    pub source: Option<SourceLine>,
+}
+
+impl fmt::Display for FaultReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "runtime fault: {}", self.error)?;
+
+        write!(f, "  at address {:0>2}", self.address)?;
+        if let Some(word) = &self.word {
+            write!(f, ": +{:0>4}", word)?;
+        }
+        if let Some(instruction) = &self.instruction {
+            write!(f, " ({})", instruction)?;
+        }
+        writeln!(f)?;
+
+        if let Some(source) = &self.source {
+            writeln!(f, "  source line {}: {}", source.number, source.text)?;
+        }
+
+        writeln!(f, "  registers: {}", self.registers)?;
+
+        let history: Vec<String> = self
+            .history
+            .iter()
+            .map(|pc| format!("{:0>2}", pc))
+            .collect();
+        write!(f, "  recent pcs: {}", history.join(" -> "))
+    }
+}
@@ -1,6 +1,18 @@
+use std::fmt;
+
 use crate::vm::operation::Opcode;
 
+#[derive(Debug, Clone, Copy)]
 pub struct Instruction {
     pub opcode: Opcode,
     pub operand: usize,
 }
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.opcode {
+            Opcode::Extension(mnemonic) => write!(f, "{} {:0>2}", mnemonic, self.operand),
+            opcode => write!(f, "{:?} {:0>2}", opcode, self.operand),
+        }
+    }
+}
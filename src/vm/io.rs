@@ -0,0 +1,267 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::Path;
+
+use crate::vm::error::SimpletronError;
+use crate::vm::machine::Output;
+
+/// Where `READ`/`READI` get their values and `WRITE`/`WRITEA` send theirs.
+pub trait IoDevice {
+    /// Next input value, or `None` at end of input.
+    fn read(&mut self) -> Result<Option<i32>, SimpletronError>;
+    fn write(&mut self, output: Output) -> Result<(), SimpletronError>;
+
+    /// Input values that were given but never read. Devices that read
+    /// from a stream count what is left in it, consuming it; devices that
+    /// read on demand have none.
+    fn unread(&mut self) -> usize {
+        0
+    }
+}
+
+impl<T: IoDevice + ?Sized> IoDevice for Box<T> {
+    fn read(&mut self) -> Result<Option<i32>, SimpletronError> {
+        (**self).read()
+    }
+
+    fn write(&mut self, output: Output) -> Result<(), SimpletronError> {
+        (**self).write(output)
+    }
+
+    fn unread(&mut self) -> usize {
+        (**self).unread()
+    }
+}
+
+/// How output values are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// `Memory[15] = 120` and `ACC: 7`.
+    #[default]
+    Labeled,
+    /// Just the number.
+    Raw,
+}
+
+impl OutputFormat {
+    pub fn render(&self, output: Output) -> String {
+        match self {
+            OutputFormat::Labeled => output.to_string(),
+            OutputFormat::Raw => output.value().to_string(),
+        }
+    }
+}
+
+/// Parses input values separated by commas or whitespace.
+pub fn parse_values(text: &str) -> Result<Vec<i32>, SimpletronError> {
+    split_values(text)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| SimpletronError::InvalidReadInput(value.to_string()))
+        })
+        .collect()
+}
+
+fn split_values(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+}
+
+/// Counts the values left in `input`, reading it to the end.
+fn count_values(input: impl BufRead) -> usize {
+    input
+        .lines()
+        .map_while(Result::ok)
+        .map(|line| split_values(&line).count())
+        .sum()
+}
+
+/// Stdin, read a line at a time as values are needed; a line may hold
+/// several values separated by commas or whitespace. On a terminal it
+/// prompts for each line, except in raw mode so output stays
+/// machine-readable. Piped input is never prompted for.
+#[derive(Debug, Default)]
+pub struct ConsoleIo {
+    format: OutputFormat,
+    pending: VecDeque<i32>,
+    /// Whether the program has read from stdin at all.
+    reading: bool,
+}
+
+impl ConsoleIo {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            pending: VecDeque::new(),
+            reading: false,
+        }
+    }
+}
+
+impl IoDevice for ConsoleIo {
+    fn read(&mut self) -> Result<Option<i32>, SimpletronError> {
+        self.reading = true;
+        while self.pending.is_empty() {
+            if self.format == OutputFormat::Labeled && io::stdin().is_terminal() {
+                print!("Enter a number: ");
+                io::stdout().flush()?;
+            }
+
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.pending.extend(parse_values(&line)?);
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    fn write(&mut self, output: Output) -> Result<(), SimpletronError> {
+        println!("{}", self.format.render(output));
+        Ok(())
+    }
+
+    fn unread(&mut self) -> usize {
+        // Reading a terminal to the end would wait for the user, and a
+        // program that never asked for input leaves stdin alone.
+        if !self.reading || io::stdin().is_terminal() {
+            return self.pending.len();
+        }
+        self.pending.len() + count_values(io::stdin().lock())
+    }
+}
+
+/// Input from a fixed list of values, output to stdout.
+#[derive(Debug, Default)]
+pub struct ScriptedIo {
+    inputs: VecDeque<i32>,
+    format: OutputFormat,
+}
+
+impl ScriptedIo {
+    pub fn new(inputs: impl IntoIterator<Item = i32>, format: OutputFormat) -> Self {
+        Self {
+            inputs: inputs.into_iter().collect(),
+            format,
+        }
+    }
+}
+
+impl IoDevice for ScriptedIo {
+    fn read(&mut self) -> Result<Option<i32>, SimpletronError> {
+        Ok(self.inputs.pop_front())
+    }
+
+    fn write(&mut self, output: Output) -> Result<(), SimpletronError> {
+        println!("{}", self.format.render(output));
+        Ok(())
+    }
+
+    fn unread(&mut self) -> usize {
+        self.inputs.len()
+    }
+}
+
+/// Input values read from a file as needed (separated by commas or
+/// whitespace), output written to a file or, without one, stdout.
+pub struct FileIo {
+    input: Option<BufReader<File>>,
+    pending: VecDeque<i32>,
+    output: Box<dyn Write>,
+    format: OutputFormat,
+}
+
+impl FileIo {
+    pub fn open(
+        input: Option<&Path>,
+        output: Option<&Path>,
+        format: OutputFormat,
+    ) -> Result<Self, SimpletronError> {
+        let input = input.map(File::open).transpose()?.map(BufReader::new);
+        let output: Box<dyn Write> = match output {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(io::stdout()),
+        };
+        Ok(Self {
+            input,
+            pending: VecDeque::new(),
+            output,
+            format,
+        })
+    }
+}
+
+impl IoDevice for FileIo {
+    fn read(&mut self) -> Result<Option<i32>, SimpletronError> {
+        while self.pending.is_empty() {
+            let Some(input) = self.input.as_mut() else {
+                return Ok(None);
+            };
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.pending.extend(parse_values(&line)?);
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    fn write(&mut self, output: Output) -> Result<(), SimpletronError> {
+        writeln!(self.output, "{}", self.format.render(output))?;
+        Ok(())
+    }
+
+    fn unread(&mut self) -> usize {
+        self.pending.len() + self.input.as_mut().map_or(0, count_values)
+    }
+}
+
+/// In-memory input and output, for driving programs from tests.
+#[derive(Debug, Default)]
+pub struct BufferIo {
+    inputs: VecDeque<i32>,
+    outputs: Vec<Output>,
+}
+
+impl BufferIo {
+    pub fn new(inputs: impl IntoIterator<Item = i32>) -> Self {
+        Self {
+            inputs: inputs.into_iter().collect(),
+            outputs: Vec::new(),
+        }
+    }
+
+    pub fn outputs(&self) -> &[Output] {
+        &self.outputs
+    }
+
+    /// Output values, without where they came from.
+    pub fn values(&self) -> Vec<i32> {
+        self.outputs.iter().map(Output::value).collect()
+    }
+
+    /// Output as it would have been printed.
+    pub fn text(&self, format: OutputFormat) -> String {
+        self.outputs
+            .iter()
+            .map(|output| format!("{}\n", format.render(*output)))
+            .collect()
+    }
+}
+
+impl IoDevice for BufferIo {
+    fn read(&mut self) -> Result<Option<i32>, SimpletronError> {
+        Ok(self.inputs.pop_front())
+    }
+
+    fn write(&mut self, output: Output) -> Result<(), SimpletronError> {
+        self.outputs.push(output);
+        Ok(())
+    }
+
+    fn unread(&mut self) -> usize {
+        self.inputs.len()
+    }
+}
@@ -0,0 +1,450 @@
+use std::fmt;
+
+use crate::vm::error::SimpletronError;
+use crate::vm::instruction::Instruction;
+use crate::vm::operation::Opcode;
+use crate::vm::semantics::{self, Semantics};
+use crate::vm::word::Word;
+
+/// What an instruction's operand refers to, which decides how the
+/// assembler reads it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    /// No operand, e.g. `WRITEA`.
+    None,
+    /// A memory cell: a variable name or an address.
+    Address,
+    /// A constant value, e.g. `LOADI 5`.
+    Immediate,
+    /// A code address: a label or an address.
+    Target,
+    /// An optional exit status from 0 to 99, e.g. `HALT 3`.
+    Status,
+}
+
+/// One entry of an instruction set: how an operation is numbered and
+/// written, what its operand means and what it does.
+#[derive(Debug, Clone, Copy)]
+pub struct OpcodeSpec {
+    pub code: i32,
+    pub mnemonic: &'static str,
+    pub operand: OperandKind,
+    /// Which operation this is, for the assembler's analyses. Operations
+    /// the crate does not know are [`Opcode::Extension`].
+    pub operation: Opcode,
+    /// What the machine does when it runs the instruction.
+    pub semantics: Semantics,
+    /// Whether it consumes an input value, so the machine waits for one
+    /// before running it.
+    pub reads_input: bool,
+}
+
+impl OpcodeSpec {
+    /// Marks the instruction as consuming an input value.
+    pub const fn reading(self) -> Self {
+        Self {
+            reads_input: true,
+            ..self
+        }
+    }
+}
+
+/// A table of opcodes. The machine decodes and runs words through it and
+/// the assembler, encoder and disassembler read and write instructions
+/// with it, so an opcode is added, renumbered or given new behavior in one
+/// place.
+///
+/// An instruction set does not have to offer every standard [`Opcode`];
+/// words whose opcode is not in the table fault as invalid. It can also
+/// add its own: an [`extension`] row carries its semantics with it.
+pub trait InstructionSet: fmt::Debug + Sync {
+    fn name(&self) -> &'static str;
+    fn opcodes(&self) -> &[OpcodeSpec];
+
+    fn by_code(&self, code: i32) -> Option<&OpcodeSpec> {
+        self.opcodes().iter().find(|spec| spec.code == code)
+    }
+
+    fn by_mnemonic(&self, mnemonic: &str) -> Option<&OpcodeSpec> {
+        self.opcodes().iter().find(|spec| spec.mnemonic == mnemonic)
+    }
+
+    fn by_operation(&self, operation: Opcode) -> Option<&OpcodeSpec> {
+        self.opcodes()
+            .iter()
+            .find(|spec| spec.operation == operation)
+    }
+
+    /// The table entry for the instruction in `word`.
+    fn lookup(&self, word: Word) -> Result<&OpcodeSpec, SimpletronError> {
+        self.by_code(word.opcode())
+            .ok_or(SimpletronError::InvalidOpcode(word.opcode()))
+    }
+
+    fn decode(&self, word: Word) -> Result<Instruction, SimpletronError> {
+        let spec = self.lookup(word)?;
+        Ok(Instruction {
+            opcode: spec.operation,
+            operand: word.operand(),
+        })
+    }
+
+    fn encode(&self, operation: Opcode, operand: usize) -> Result<Word, SimpletronError> {
+        let spec =
+            self.by_operation(operation)
+                .ok_or_else(|| SimpletronError::UnsupportedOperation {
+                    operation,
+                    instruction_set: self.name(),
+                })?;
+        Ok(Word::new(spec.code * 100 + operand as i32))
+    }
+
+    /// The assembly text for `word`, e.g. `LOADM 14`, or `None` if it is
+    /// not an instruction.
+    fn disassemble(&self, word: Word) -> Option<String> {
+        if word.value() < 0 {
+            return None;
+        }
+        let spec = self.by_code(word.opcode())?;
+        let operand = word.operand();
+        Some(match spec.operand {
+            OperandKind::None => spec.mnemonic.to_string(),
+            OperandKind::Status if operand == 0 => spec.mnemonic.to_string(),
+            _ => format!("{} {:0>2}", spec.mnemonic, operand),
+        })
+    }
+}
+
+/// The Simpletron instruction set this crate assembles and runs by
+/// default.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Simpletron;
+
+const SIMPLETRON: [OpcodeSpec; 23] = [
+    spec(
+        10,
+        "READ",
+        OperandKind::Address,
+        Opcode::Read,
+        semantics::read,
+    )
+    .reading(),
+    spec(
+        11,
+        "WRITE",
+        OperandKind::Address,
+        Opcode::Write,
+        semantics::write,
+    ),
+    spec(
+        12,
+        "WRITEA",
+        OperandKind::None,
+        Opcode::WriteAcc,
+        semantics::write_acc,
+    ),
+    spec(
+        13,
+        "READI",
+        OperandKind::None,
+        Opcode::ReadI,
+        semantics::read_i,
+    )
+    .reading(),
+    spec(
+        20,
+        "LOADM",
+        OperandKind::Address,
+        Opcode::LoadM,
+        semantics::load_m,
+    ),
+    spec(
+        21,
+        "STORE",
+        OperandKind::Address,
+        Opcode::Store,
+        semantics::store,
+    ),
+    spec(
+        22,
+        "LOADI",
+        OperandKind::Immediate,
+        Opcode::LoadI,
+        semantics::load_i,
+    ),
+    spec(
+        30,
+        "ADDM",
+        OperandKind::Address,
+        Opcode::AddM,
+        semantics::add_m,
+    ),
+    spec(
+        31,
+        "SUBM",
+        OperandKind::Address,
+        Opcode::SubM,
+        semantics::sub_m,
+    ),
+    spec(
+        32,
+        "DIVM",
+        OperandKind::Address,
+        Opcode::DivM,
+        semantics::div_m,
+    ),
+    spec(
+        33,
+        "MODM",
+        OperandKind::Address,
+        Opcode::ModM,
+        semantics::mod_m,
+    ),
+    spec(
+        34,
+        "MULM",
+        OperandKind::Address,
+        Opcode::MulM,
+        semantics::mul_m,
+    ),
+    spec(
+        35,
+        "ADDI",
+        OperandKind::Immediate,
+        Opcode::AddI,
+        semantics::add_i,
+    ),
+    spec(
+        36,
+        "SUBI",
+        OperandKind::Immediate,
+        Opcode::SubI,
+        semantics::sub_i,
+    ),
+    spec(
+        37,
+        "DIVI",
+        OperandKind::Immediate,
+        Opcode::DivI,
+        semantics::div_i,
+    ),
+    spec(
+        38,
+        "MODI",
+        OperandKind::Immediate,
+        Opcode::ModI,
+        semantics::mod_i,
+    ),
+    spec(
+        39,
+        "MULI",
+        OperandKind::Immediate,
+        Opcode::MulI,
+        semantics::mul_i,
+    ),
+    spec(
+        40,
+        "JMP",
+        OperandKind::Target,
+        Opcode::Jump,
+        semantics::jump,
+    ),
+    spec(
+        41,
+        "JN",
+        OperandKind::Target,
+        Opcode::JumpIfNegative,
+        semantics::jump_if_negative,
+    ),
+    spec(
+        42,
+        "JZ",
+        OperandKind::Target,
+        Opcode::JumpIfZero,
+        semantics::jump_if_zero,
+    ),
+    spec(
+        43,
+        "HALT",
+        OperandKind::Status,
+        Opcode::Halt,
+        semantics::halt,
+    ),
+    spec(
+        44,
+        "JNZ",
+        OperandKind::Target,
+        Opcode::JumpIfNotZero,
+        semantics::jump_if_not_zero,
+    ),
+    spec(
+        45,
+        "JG",
+        OperandKind::Target,
+        Opcode::JumpIfGreaterThanZero,
+        semantics::jump_if_greater_than_zero,
+    ),
+];
+
+impl InstructionSet for Simpletron {
+    fn name(&self) -> &'static str {
+        "simpletron"
+    }
+
+    fn opcodes(&self) -> &[OpcodeSpec] {
+        &SIMPLETRON
+    }
+
+    fn by_code(&self, code: i32) -> Option<&OpcodeSpec> {
+        // Codes are sorted, so skip the linear scan on the decode path.
+        SIMPLETRON
+            .binary_search_by_key(&code, |spec| spec.code)
+            .ok()
+            .map(|index| &SIMPLETRON[index])
+    }
+}
+
+/// The original Simpletron Machine Language from Deitel's exercises: twelve
+/// operations, memory operands only, and `MULTIPLY` at 33 where this crate
+/// has `MODM`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ClassicSml;
+
+const CLASSIC_SML: [OpcodeSpec; 12] = [
+    spec(
+        10,
+        "READ",
+        OperandKind::Address,
+        Opcode::Read,
+        semantics::read,
+    )
+    .reading(),
+    spec(
+        11,
+        "WRITE",
+        OperandKind::Address,
+        Opcode::Write,
+        semantics::write,
+    ),
+    spec(
+        20,
+        "LOAD",
+        OperandKind::Address,
+        Opcode::LoadM,
+        semantics::load_m,
+    ),
+    spec(
+        21,
+        "STORE",
+        OperandKind::Address,
+        Opcode::Store,
+        semantics::store,
+    ),
+    spec(
+        30,
+        "ADD",
+        OperandKind::Address,
+        Opcode::AddM,
+        semantics::add_m,
+    ),
+    spec(
+        31,
+        "SUBTRACT",
+        OperandKind::Address,
+        Opcode::SubM,
+        semantics::sub_m,
+    ),
+    spec(
+        32,
+        "DIVIDE",
+        OperandKind::Address,
+        Opcode::DivM,
+        semantics::div_m,
+    ),
+    spec(
+        33,
+        "MULTIPLY",
+        OperandKind::Address,
+        Opcode::MulM,
+        semantics::mul_m,
+    ),
+    spec(
+        40,
+        "BRANCH",
+        OperandKind::Target,
+        Opcode::Jump,
+        semantics::jump,
+    ),
+    spec(
+        41,
+        "BRANCHNEG",
+        OperandKind::Target,
+        Opcode::JumpIfNegative,
+        semantics::jump_if_negative,
+    ),
+    spec(
+        42,
+        "BRANCHZERO",
+        OperandKind::Target,
+        Opcode::JumpIfZero,
+        semantics::jump_if_zero,
+    ),
+    spec(43, "HALT", OperandKind::None, Opcode::Halt, semantics::halt),
+];
+
+impl InstructionSet for ClassicSml {
+    fn name(&self) -> &'static str {
+        "sml"
+    }
+
+    fn opcodes(&self) -> &[OpcodeSpec] {
+        &CLASSIC_SML
+    }
+}
+
+/// The built-in instruction sets, for `--isa`.
+pub const INSTRUCTION_SETS: [&dyn InstructionSet; 2] = [&Simpletron, &ClassicSml];
+
+/// Looks up a built-in instruction set by name.
+pub fn by_name(name: &str) -> Result<&'static dyn InstructionSet, SimpletronError> {
+    INSTRUCTION_SETS
+        .into_iter()
+        .find(|isa| isa.name() == name)
+        .ok_or_else(|| SimpletronError::UnknownInstructionSet(name.to_string()))
+}
+
+/// A table row for a standard operation.
+pub const fn spec(
+    code: i32,
+    mnemonic: &'static str,
+    operand: OperandKind,
+    operation: Opcode,
+    semantics: Semantics,
+) -> OpcodeSpec {
+    OpcodeSpec {
+        code,
+        mnemonic,
+        operand,
+        operation,
+        semantics,
+        reads_input: false,
+    }
+}
+
+/// A table row for an operation only this instruction set has. The
+/// assembler does not try to optimize, pack or flow-check programs that
+/// use one, since it cannot tell what the instruction does.
+pub const fn extension(
+    code: i32,
+    mnemonic: &'static str,
+    operand: OperandKind,
+    semantics: Semantics,
+) -> OpcodeSpec {
+    spec(
+        code,
+        mnemonic,
+        operand,
+        Opcode::Extension(mnemonic),
+        semantics,
+    )
+}
@@ -0,0 +1,94 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::Duration;
+
+use crate::vm::word::Word;
+
+/// Bounds on how long a [`crate::vm::machine::Machine`] may run. All are
+/// off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Instructions executed before the run faults.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time, measured from the first step, before the run faults.
+    pub timeout: Option<Duration>,
+    /// Fault as soon as the machine is provably stuck in an infinite loop.
+    pub detect_loops: bool,
+}
+
+/// Everything that decides what the machine does next, given no more
+/// input.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct MachineState {
+    pub pc: usize,
+    pub accumulator: i32,
+    pub memory: Vec<Word>,
+    pub input: Vec<i32>,
+}
+
+/// Exact infinite-loop detection over the states seen at taken backward
+/// jumps.
+///
+/// A program that runs forever without reading input must take backward
+/// jumps forever, and since it is deterministic it repeats a state at one
+/// of them. Only one checkpoint state is kept, replaced after 1, 2, 4, ...
+/// further backward jumps (Brent's method), so memory use stays constant
+/// and any cycle is found within about twice its length. Consuming input
+/// changes what comes next, so it starts the search over.
+#[derive(Debug)]
+pub(crate) struct LoopDetector {
+    checkpoint: Option<(u64, MachineState)>,
+    interval: u64,
+    since_checkpoint: u64,
+    low: usize,
+    high: usize,
+}
+
+impl Default for LoopDetector {
+    fn default() -> Self {
+        Self {
+            checkpoint: None,
+            interval: 1,
+            since_checkpoint: 0,
+            low: usize::MAX,
+            high: 0,
+        }
+    }
+}
+
+impl LoopDetector {
+    /// Notes an executed address, so a detected loop can report its range.
+    pub fn visit(&mut self, address: usize) {
+        self.low = self.low.min(address);
+        self.high = self.high.max(address);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Checks the state right after a taken backward jump. Returns the
+    /// lowest and highest address of the loop if the state repeats the
+    /// checkpoint.
+    pub fn backward_jump(&mut self, state: MachineState) -> Option<(usize, usize)> {
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if let Some((checkpoint_hash, checkpoint)) = &self.checkpoint
+            && *checkpoint_hash == hash
+            && *checkpoint == state
+        {
+            return Some((self.low, self.high));
+        }
+
+        self.since_checkpoint += 1;
+        if self.checkpoint.is_none() || self.since_checkpoint >= self.interval {
+            self.low = state.pc;
+            self.high = state.pc;
+            self.checkpoint = Some((hash, state));
+            self.interval *= 2;
+            self.since_checkpoint = 0;
+        }
+        None
+    }
+}
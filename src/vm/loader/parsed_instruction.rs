@@ -1,22 +1,17 @@
-use crate::vm::{error::SimpletronError, instruction::Instruction, operation::Opcode};
+use crate::vm::{error::SimpletronError, instruction::Instruction, operation::Opcode, word::Word};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct ParsedInstruction {
     pub address: usize,
-    pub data: String,
+    pub data: Word,
 }
 
 impl TryFrom<ParsedInstruction> for Instruction {
     type Error = SimpletronError;
 
     fn try_from(value: ParsedInstruction) -> Result<Self, Self::Error> {
-        let raw: i32 = value
-            .data
-            .parse()
-            .map_err(|_| SimpletronError::InvalidInstruction(value.data.clone()))?;
-
-        let opcode = Opcode::try_from(raw / 100)?;
-        let operand = (raw % 100) as usize;
+        let opcode = Opcode::try_from(value.data.opcode())?;
+        let operand = value.data.operand();
 
         Ok(Self { opcode, operand })
     }
@@ -0,0 +1,557 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Instant;
+
+use crate::vm::debug_info::DebugInfo;
+use crate::vm::decode::{DecodeCache, Decoded};
+use crate::vm::error::SimpletronError;
+use crate::vm::fault::{FaultReport, PC_HISTORY_LEN};
+use crate::vm::instruction::Instruction;
+use crate::vm::isa::{InstructionSet, Simpletron};
+use crate::vm::limits::{Limits, LoopDetector, MachineState};
+use crate::vm::loader::ParsedInstruction;
+use crate::vm::memory::{MemoryInterface, MemoryLoader, MemoryPayload};
+use crate::vm::overflow::OverflowPolicy;
+use crate::vm::processor::{ProcessorInterface, Registers};
+use crate::vm::semantics::{Effect, Execution};
+
+/// What happened when the machine tried to execute one instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The instruction ran and the machine can keep going.
+    Continued,
+    /// `HALT` ran; see [`Machine::exit_status`]. Further steps do nothing.
+    Halted,
+    /// The instruction failed; see [`Machine::fault`]. Further steps do
+    /// nothing.
+    Fault,
+    /// A `READ` or `READI` is waiting for [`Machine::push_input`]. Nothing
+    /// was executed.
+    AwaitingInput,
+}
+
+/// A value the program wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// `WRITE addr`.
+    Memory { address: usize, value: i32 },
+    /// `WRITEA`.
+    Accumulator(i32),
+}
+
+impl Output {
+    pub fn value(&self) -> i32 {
+        match self {
+            Output::Memory { value, .. } | Output::Accumulator(value) => *value,
+        }
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Memory { address, value } => write!(f, "Memory[{}] = {}", address, value),
+            Output::Accumulator(value) => write!(f, "ACC: {}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Running,
+    Halted,
+    Faulted,
+}
+
+/// A processor and its memory, executed one instruction at a time.
+///
+/// The machine does no I/O of its own: input is queued with
+/// [`Machine::push_input`] and output is collected for
+/// [`Machine::take_output`], so it can be driven from a console, a test or
+/// another program.
+pub struct Machine<P, M>
+where
+    P: ProcessorInterface,
+    M: MemoryInterface,
+{
+    cpu: P,
+    memory: M,
+    debug_info: Option<DebugInfo>,
+    history: VecDeque<usize>,
+    input: VecDeque<i32>,
+    output: Vec<Output>,
+    status: Status,
+    exit_status: Option<u8>,
+    fault: Option<Box<FaultReport>>,
+    limits: Limits,
+    overflow: OverflowPolicy,
+    steps: u64,
+    started: Option<Instant>,
+    loops: LoopDetector,
+    isa: &'static dyn InstructionSet,
+    decoded: DecodeCache,
+    fusion: bool,
+}
+
+impl<P, M> Machine<P, M>
+where
+    P: ProcessorInterface,
+    M: MemoryInterface,
+{
+    pub fn new(cpu: P, memory: M) -> Self {
+        Self {
+            cpu,
+            memory,
+            debug_info: None,
+            history: VecDeque::with_capacity(PC_HISTORY_LEN),
+            input: VecDeque::new(),
+            output: Vec::new(),
+            status: Status::Running,
+            exit_status: None,
+            fault: None,
+            limits: Limits::default(),
+            overflow: OverflowPolicy::default(),
+            steps: 0,
+            started: None,
+            loops: LoopDetector::default(),
+            isa: &Simpletron,
+            decoded: DecodeCache::default(),
+            fusion: false,
+        }
+    }
+
+    /// Loads a word image starting at address 0.
+    pub fn load(&mut self, words: &[u16]) -> Result<(), SimpletronError> {
+        self.decoded.clear();
+        MemoryLoader::new(&mut self.memory, false).load_program(words)
+    }
+
+    /// Source lines used in fault reports.
+    pub fn set_debug_info(&mut self, debug_info: DebugInfo) {
+        self.debug_info = Some(debug_info);
+    }
+
+    /// Instruction budget, time limit and loop detection for later steps.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.loops.reset();
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// How arithmetic results and input values are fitted to a word.
+    pub fn set_overflow(&mut self, policy: OverflowPolicy) {
+        self.overflow = policy;
+    }
+
+    pub fn overflow(&self) -> OverflowPolicy {
+        self.overflow
+    }
+
+    /// The opcode table used to decode memory, [`Simpletron`] by default.
+    pub fn set_instruction_set(&mut self, isa: &'static dyn InstructionSet) {
+        self.isa = isa;
+        self.decoded.clear();
+    }
+
+    pub fn instruction_set(&self) -> &'static dyn InstructionSet {
+        self.isa
+    }
+
+    /// Whether [`Machine::run`] and [`Machine::run_for`] execute the common
+    /// pairs listed on `decode::Decoded` as one superinstruction. Off by
+    /// default, since every instruction of a pair still does its own
+    /// bookkeeping and the pair measured no faster; results are the same
+    /// either way.
+    pub fn set_fusion(&mut self, enabled: bool) {
+        self.fusion = enabled;
+    }
+
+    /// Instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn cpu(&self) -> &P {
+        &self.cpu
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    pub fn pc(&self) -> usize {
+        self.cpu.get_pc()
+    }
+
+    pub fn set_pc(&mut self, address: usize) -> Result<(), SimpletronError> {
+        self.loops.reset();
+        self.cpu.set_pc(address)
+    }
+
+    pub fn accumulator(&self) -> i32 {
+        self.cpu.get_acc_value()
+    }
+
+    pub fn set_accumulator(&mut self, value: i32) {
+        self.loops.reset();
+        self.cpu.write_acc(value);
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.cpu.registers()
+    }
+
+    pub fn read_memory(&self, address: usize) -> Result<i32, SimpletronError> {
+        Ok(self.memory.read_data(address)?.value())
+    }
+
+    pub fn write_memory(&mut self, address: usize, value: i32) -> Result<(), SimpletronError> {
+        self.loops.reset();
+        self.store(address, value)
+    }
+
+    fn store(&mut self, address: usize, value: i32) -> Result<(), SimpletronError> {
+        self.decoded.invalidate(address);
+        self.memory.store_data(MemoryPayload::new(address, value))
+    }
+
+    /// Queues a value for the next `READ` or `READI`.
+    pub fn push_input(&mut self, value: i32) {
+        self.input.push_back(value);
+    }
+
+    /// Number of queued input values not yet read.
+    pub fn pending_input(&self) -> usize {
+        self.input.len()
+    }
+
+    /// Output written since the last call.
+    pub fn take_output(&mut self) -> Vec<Output> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.status == Status::Halted
+    }
+
+    /// The operand of the `HALT` that stopped the machine, if it halted.
+    pub fn exit_status(&self) -> Option<u8> {
+        self.exit_status
+    }
+
+    /// The fault that stopped the machine, if any.
+    pub fn fault(&self) -> Option<&FaultReport> {
+        self.fault.as_deref()
+    }
+
+    pub fn take_fault(&mut self) -> Option<FaultReport> {
+        self.fault.take().map(|fault| *fault)
+    }
+
+    /// The instruction at the program counter, if it decodes.
+    pub fn current_instruction(&self) -> Option<Instruction> {
+        let word = self.memory.read_data(self.cpu.get_pc()).ok()?;
+        self.isa.decode(word).ok()
+    }
+
+    /// Whether the next instruction reads input that has not been queued.
+    pub fn needs_input(&self) -> bool {
+        self.input.is_empty()
+            && self
+                .memory
+                .read_data(self.cpu.get_pc())
+                .ok()
+                .and_then(|word| self.isa.lookup(word).ok())
+                .is_some_and(|spec| spec.reads_input)
+    }
+
+    /// Executes one instruction.
+    pub fn step(&mut self) -> StepOutcome {
+        self.advance(1).0
+    }
+
+    /// Steps until `done` holds (checked before each step) or the machine
+    /// stops. Returns `Continued` if `done` ended the run.
+    pub fn run_until(&mut self, mut done: impl FnMut(&Self) -> bool) -> StepOutcome {
+        loop {
+            if done(self) {
+                return StepOutcome::Continued;
+            }
+            let outcome = self.step();
+            if outcome != StepOutcome::Continued {
+                return outcome;
+            }
+        }
+    }
+
+    /// Executes at most `steps` instructions, fusing pairs where it can.
+    pub fn run_for(&mut self, steps: usize) -> StepOutcome {
+        let mut remaining = steps;
+        while remaining > 0 {
+            let (outcome, executed) = self.advance(remaining);
+            if outcome != StepOutcome::Continued {
+                return outcome;
+            }
+            remaining -= executed;
+        }
+        StepOutcome::Continued
+    }
+
+    /// Runs until the machine halts, faults or waits for input, fusing
+    /// pairs where it can.
+    pub fn run(&mut self) -> StepOutcome {
+        loop {
+            let outcome = self.run_for(usize::MAX);
+            if outcome != StepOutcome::Continued {
+                return outcome;
+            }
+        }
+    }
+
+    /// Stops the machine with `error` at the current instruction, as if it
+    /// had failed there. Used by front ends whose own I/O fails. The
+    /// instruction is loaded into the registers first, so the report shows
+    /// the one that was waiting rather than the last one that ran.
+    pub fn fail(&mut self, error: SimpletronError) -> StepOutcome {
+        let address = self.cpu.get_pc();
+        self.record_pc(address);
+        if let Ok(data) = self.memory.read_data(address) {
+            let _ = self.cpu.update_state(&ParsedInstruction { address, data });
+        }
+        self.fault = Some(Box::new(self.fault_report(address, error)));
+        self.status = Status::Faulted;
+        StepOutcome::Fault
+    }
+
+    /// Executes the next instruction, or the next fused pair if
+    /// `remaining` allows two. Returns the outcome and how many
+    /// instructions ran.
+    fn advance(&mut self, remaining: usize) -> (StepOutcome, usize) {
+        match self.status {
+            Status::Halted => return (StepOutcome::Halted, 0),
+            Status::Faulted => return (StepOutcome::Fault, 0),
+            Status::Running => {}
+        }
+
+        let address = self.cpu.get_pc();
+        match self.execute_next(address, remaining) {
+            Ok(result) => result,
+            Err((at, error)) => {
+                self.fault = Some(Box::new(self.fault_report(at, error)));
+                self.status = Status::Faulted;
+                (StepOutcome::Fault, 1)
+            }
+        }
+    }
+
+    fn execute_next(
+        &mut self,
+        address: usize,
+        remaining: usize,
+    ) -> Result<(StepOutcome, usize), (usize, SimpletronError)> {
+        let at = |address: usize| move |error| (address, error);
+
+        let decoded = match self.decoded.get(self.isa, &self.memory, address) {
+            Ok(decoded) => decoded,
+            Err(error) => {
+                return self
+                    .execute_undecodable(address, error)
+                    .map(|outcome| (outcome, 1))
+                    .map_err(at(address));
+            }
+        };
+
+        if decoded.spec.reads_input && self.input.is_empty() {
+            return Ok((StepOutcome::AwaitingInput, 0));
+        }
+        self.check_limits().map_err(at(address))?;
+
+        if decoded.fuses
+            && self.fusion
+            && remaining >= 2
+            && self
+                .limits
+                .max_steps
+                .is_none_or(|limit| self.steps + 2 <= limit)
+        {
+            return self
+                .execute_fused(address, &decoded)
+                .map(|outcome| (outcome, 2));
+        }
+
+        self.begin(address, &decoded).map_err(at(address))?;
+        self.execute(address, &decoded)
+            .map(|outcome| (outcome, 1))
+            .map_err(at(address))
+    }
+
+    /// Executes the pair at `address` with the same effects, counts and
+    /// fault addresses as two separate steps, without going back through
+    /// the limit and input checks in between.
+    fn execute_fused(
+        &mut self,
+        address: usize,
+        first: &Decoded,
+    ) -> Result<StepOutcome, (usize, SimpletronError)> {
+        let second = address + 1;
+        let at = |address: usize| move |error| (address, error);
+
+        self.begin(address, first).map_err(at(address))?;
+        self.execute(address, first).map_err(at(address))?;
+
+        let next = self
+            .decoded
+            .get(self.isa, &self.memory, second)
+            .map_err(at(second))?;
+        self.begin(second, &next).map_err(at(second))?;
+        self.execute(second, &next).map_err(at(second))
+    }
+
+    /// Bookkeeping shared by every executed instruction.
+    fn begin(&mut self, address: usize, decoded: &Decoded) -> Result<(), SimpletronError> {
+        self.record_pc(address);
+        self.steps += 1;
+        self.cpu.update_state(&decoded.parsed(address))
+    }
+
+    /// A word that does not decode still counts as executed, then faults.
+    fn execute_undecodable(
+        &mut self,
+        address: usize,
+        error: SimpletronError,
+    ) -> Result<StepOutcome, SimpletronError> {
+        let data = self.memory.read_data(address)?;
+        self.check_limits()?;
+
+        self.record_pc(address);
+        self.steps += 1;
+        self.cpu
+            .update_state(&ParsedInstruction { address, data })?;
+        Err(error)
+    }
+
+    fn check_limits(&mut self) -> Result<(), SimpletronError> {
+        if let Some(limit) = self.limits.max_steps
+            && self.steps >= limit
+        {
+            return Err(SimpletronError::InstructionLimit { limit });
+        }
+        if let Some(limit) = self.limits.timeout
+            && self.started.get_or_insert_with(Instant::now).elapsed() > limit
+        {
+            return Err(SimpletronError::Timeout { limit });
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> Result<MachineState, SimpletronError> {
+        Ok(MachineState {
+            pc: self.cpu.get_pc(),
+            accumulator: self.cpu.get_acc_value(),
+            memory: (0..self.memory.get_memory_length())
+                .map(|address| self.memory.read_data(address))
+                .collect::<Result<_, _>>()?,
+            input: self.input.iter().copied().collect(),
+        })
+    }
+
+    fn record_pc(&mut self, address: usize) {
+        if self.limits.detect_loops {
+            self.loops.visit(address);
+        }
+        if self.history.len() == PC_HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(address);
+    }
+
+    fn fault_report(&self, address: usize, error: SimpletronError) -> FaultReport {
+        let word = self.memory.read_data(address).ok();
+        let instruction = word.and_then(|word| self.isa.decode(word).ok());
+        let source = self
+            .debug_info
+            .as_ref()
+            .and_then(|info| info.lookup(address))
+            .cloned();
+
+        FaultReport {
+            error,
+            address,
+            word,
+            instruction,
+            registers: self.cpu.registers(),
+            history: self.history.iter().copied().collect(),
+            source,
+        }
+    }
+
+    /// Runs the instruction's semantics from its table entry and moves the
+    /// program counter on.
+    fn execute(
+        &mut self,
+        address: usize,
+        decoded: &Decoded,
+    ) -> Result<StepOutcome, SimpletronError> {
+        match (decoded.spec.semantics)(self, decoded.instruction.operand)? {
+            Effect::Next => self.cpu.increment_pc(),
+            Effect::Jump(target) => self.jump(address, target)?,
+            Effect::Halt(status) => {
+                self.status = Status::Halted;
+                self.exit_status = Some(status);
+                return Ok(StepOutcome::Halted);
+            }
+        }
+        Ok(StepOutcome::Continued)
+    }
+
+    fn jump(&mut self, from: usize, address: usize) -> Result<(), SimpletronError> {
+        self.cpu.set_pc(address)?;
+        if self.limits.detect_loops
+            && address <= from
+            && let Some((start, end)) = self.loops.backward_jump(self.state()?)
+        {
+            return Err(SimpletronError::InfiniteLoop { start, end });
+        }
+        Ok(())
+    }
+}
+
+impl<P, M> Execution for Machine<P, M>
+where
+    P: ProcessorInterface,
+    M: MemoryInterface,
+{
+    fn acc(&self) -> i32 {
+        self.cpu.get_acc_value()
+    }
+
+    fn set_acc(&mut self, value: i32) {
+        self.cpu.write_acc(value);
+    }
+
+    fn read_cell(&self, address: usize) -> Result<i32, SimpletronError> {
+        self.read_memory(address)
+    }
+
+    fn write_cell(&mut self, address: usize, value: i32) -> Result<(), SimpletronError> {
+        self.store(address, value)
+    }
+
+    fn next_input(&mut self) -> Result<i32, SimpletronError> {
+        let value = self.input.pop_front().expect("input was checked");
+        let value = self.overflow.apply(value as i64)?;
+        self.loops.reset();
+        Ok(value)
+    }
+
+    fn write_output(&mut self, output: Output) {
+        self.output.push(output);
+    }
+
+    fn fit(&self, value: i64) -> Result<i32, SimpletronError> {
+        self.overflow.apply(value)
+    }
+}
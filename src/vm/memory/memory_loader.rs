@@ -24,7 +24,7 @@ where
         }
 
         for (address, word) in program.iter().enumerate() {
-            self.memory.store_data(MemoryPayload::new(address, word))?;
+            self.memory.store_data(MemoryPayload::new(address, *word))?;
         }
 
         Ok(())
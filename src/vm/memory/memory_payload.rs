@@ -1,33 +1,15 @@
+use crate::vm::word::Word;
+
 pub struct MemoryPayload {
     pub address: usize,
-    pub data: MemoryData,
-}
-
-pub struct MemoryData {
-    pub value: String,
-}
-
-impl From<isize> for MemoryData {
-    fn from(value: isize) -> Self {
-        MemoryData {
-            value: value.to_string(),
-        }
-    }
+    pub data: Word,
 }
 
 impl MemoryPayload {
-    pub fn new(address: usize, word: &u16) -> Self {
+    pub fn new(address: usize, data: impl Into<Word>) -> Self {
         MemoryPayload {
             address,
-            data: MemoryData::new(word),
-        }
-    }
-}
-
-impl MemoryData {
-    pub fn new(word: &u16) -> Self {
-        MemoryData {
-            value: word.to_string(),
+            data: data.into(),
         }
     }
 }
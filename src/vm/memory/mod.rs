@@ -5,6 +5,5 @@ pub mod single_list;
 
 pub use memory_interface::MemoryInterface;
 pub use memory_loader::MemoryLoader;
-pub use memory_payload::MemoryData;
 pub use memory_payload::MemoryPayload;
 pub use single_list::SimpleMemory;
@@ -1,18 +1,17 @@
 use crate::vm::error::SimpletronError;
 use crate::vm::memory::MemoryInterface;
 use crate::vm::memory::MemoryPayload;
-
-const DEFAULT_CELL: &str = "0";
+use crate::vm::word::Word;
 
 pub struct SimpleMemory {
-    pub memory: Vec<String>,
+    pub memory: Vec<Word>,
 }
 
 impl SimpleMemory {
     pub fn new(size: Option<u16>) -> Self {
         let memory = match size {
-            Some(size) => vec![DEFAULT_CELL.to_string(); size as usize],
-            None => vec!["0".to_string(); 100 as usize],
+            Some(size) => vec![Word::ZERO; size as usize],
+            None => vec![Word::ZERO; 100],
         };
 
         SimpleMemory { memory }
@@ -67,13 +66,13 @@ impl MemoryInterface for SimpleMemory {
             ));
         }
 
-        self.memory[payload.address] = payload.data.value;
+        self.memory[payload.address] = payload.data;
         Ok(())
     }
 
-    fn read_data(&self, address: usize) -> Result<String, SimpletronError> {
+    fn read_data(&self, address: usize) -> Result<Word, SimpletronError> {
         match self.is_valid_address(address) {
-            true => Ok(self.memory[address].clone()),
+            true => Ok(self.memory[address]),
             false => Err(SimpletronError::InvalidAddressError(address.to_string())),
         }
     }
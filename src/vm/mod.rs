@@ -1,6 +1,17 @@
+pub mod debug_info;
+mod decode;
 pub mod error;
+pub mod fault;
 pub mod instruction;
+pub mod io;
+pub mod isa;
+pub mod limits;
 pub mod loader;
+pub mod machine;
 pub mod memory;
 pub mod operation;
+pub mod overflow;
 pub mod processor;
+pub mod semantics;
+pub mod trace;
+pub mod word;
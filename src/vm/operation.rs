@@ -1,6 +1,9 @@
 use crate::vm::error::SimpletronError;
+use crate::vm::isa::{InstructionSet, Simpletron};
 
-#[derive(Debug, Clone, Copy)]
+/// An operation the machine can execute. Which number and mnemonic each
+/// one has, and what it does, is up to the [`InstructionSet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Opcode {
     Read,
     Write,
@@ -25,39 +28,32 @@ pub enum Opcode {
     Halt,
     JumpIfNotZero,
     JumpIfGreaterThanZero,
+    /// An operation that only its instruction set knows, by mnemonic. The
+    /// table entry supplies everything about it.
+    Extension(&'static str),
 }
 
+impl Opcode {
+    /// The operation's mnemonic in the standard [`Simpletron`] set.
+    pub fn mnemonic(&self) -> &'static str {
+        if let Opcode::Extension(mnemonic) = self {
+            return mnemonic;
+        }
+        Simpletron
+            .by_operation(*self)
+            .map(|spec| spec.mnemonic)
+            .expect("the standard set covers every operation")
+    }
+}
+
+/// Decodes an opcode number with the standard [`Simpletron`] set.
 impl TryFrom<i32> for Opcode {
     type Error = SimpletronError;
 
     fn try_from(code: i32) -> Result<Self, Self::Error> {
-        use Opcode::*;
-
-        match code {
-            10 => Ok(Read),
-            11 => Ok(Write),
-            12 => Ok(WriteAcc),
-            13 => Ok(ReadI),
-            20 => Ok(LoadM),
-            21 => Ok(Store),
-            22 => Ok(LoadI),
-            30 => Ok(AddM),
-            31 => Ok(SubM),
-            32 => Ok(DivM),
-            33 => Ok(ModM),
-            34 => Ok(MulM),
-            35 => Ok(AddI),
-            36 => Ok(SubI),
-            37 => Ok(DivI),
-            38 => Ok(ModI),
-            39 => Ok(MulI),
-            40 => Ok(Jump),
-            41 => Ok(JumpIfNegative),
-            42 => Ok(JumpIfZero),
-            43 => Ok(Halt),
-            44 => Ok(JumpIfNotZero),
-            45 => Ok(JumpIfGreaterThanZero),
-            _ => Err(SimpletronError::InvalidOpcode(code)),
-        }
+        Simpletron
+            .by_code(code)
+            .map(|spec| spec.operation)
+            .ok_or(SimpletronError::InvalidOpcode(code))
     }
 }
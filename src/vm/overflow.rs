@@ -0,0 +1,64 @@
+use std::str::FromStr;
+
+use crate::vm::error::SimpletronError;
+
+/// Largest magnitude a 4-digit decimal word holds.
+pub const WORD_LIMIT: i32 = 9999;
+
+/// What happens when an arithmetic result or an input value does not fit in
+/// a word.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Stop with an `Overflow` fault, like the textbook machine.
+    #[default]
+    Trap,
+    /// Keep the low four digits and the sign: 12345 becomes 2345.
+    Wrap,
+    /// Clamp to -9999 or 9999.
+    Saturate,
+    /// Allow any `i32`; only a result beyond that traps.
+    Unbounded,
+}
+
+impl OverflowPolicy {
+    pub const ALL: [OverflowPolicy; 4] = [
+        OverflowPolicy::Trap,
+        OverflowPolicy::Wrap,
+        OverflowPolicy::Saturate,
+        OverflowPolicy::Unbounded,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OverflowPolicy::Trap => "trap",
+            OverflowPolicy::Wrap => "wrap",
+            OverflowPolicy::Saturate => "saturate",
+            OverflowPolicy::Unbounded => "unbounded",
+        }
+    }
+
+    /// The word stored for `value` under this policy.
+    pub fn apply(&self, value: i64) -> Result<i32, SimpletronError> {
+        let limit = WORD_LIMIT as i64;
+        match self {
+            OverflowPolicy::Trap if (-limit..=limit).contains(&value) => Ok(value as i32),
+            OverflowPolicy::Wrap => Ok((value % (limit + 1)) as i32),
+            OverflowPolicy::Saturate => Ok(value.clamp(-limit, limit) as i32),
+            OverflowPolicy::Unbounded => {
+                i32::try_from(value).map_err(|_| SimpletronError::Overflow { value })
+            }
+            OverflowPolicy::Trap => Err(SimpletronError::Overflow { value }),
+        }
+    }
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = SimpletronError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        OverflowPolicy::ALL
+            .into_iter()
+            .find(|policy| policy.name() == name)
+            .ok_or_else(|| SimpletronError::UnknownOverflowPolicy(name.to_string()))
+    }
+}
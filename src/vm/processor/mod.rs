@@ -2,4 +2,5 @@ pub mod processor_interface;
 pub mod simple_processor;
 
 pub use processor_interface::ProcessorInterface;
+pub use processor_interface::Registers;
 pub use simple_processor::SimpleProcessor;
@@ -1,4 +1,6 @@
-use crate::vm::{error::SimpletronError, loader::ParsedInstruction};
+use std::fmt;
+
+use crate::vm::{error::SimpletronError, loader::ParsedInstruction, word::Word};
 
 pub trait ProcessorInterface {
     fn increment_pc(&mut self);
@@ -8,4 +10,29 @@ pub trait ProcessorInterface {
     fn write_acc(&mut self, value: i32);
     fn set_pc(&mut self, value: usize) -> Result<(), SimpletronError>;
     fn get_pc(&self) -> usize;
+    fn registers(&self) -> Registers;
+}
+
+/// Snapshot of the processor registers.
+#[derive(Debug, Clone)]
+pub struct Registers {
+    pub accumulator: i32,
+    pub program_counter: usize,
+    pub instruction_register: Word,
+    pub opcode: i32,
+    pub operand: usize,
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "acc=+{:0>4} pc={:0>2} ir=+{:0>4} opcode={:0>2} operand={:0>2}",
+            self.accumulator,
+            self.program_counter,
+            self.instruction_register,
+            self.opcode,
+            self.operand
+        )
+    }
 }
@@ -1,13 +1,14 @@
 use crate::vm::error::SimpletronError;
 use crate::vm::loader::ParsedInstruction;
-use crate::vm::processor::ProcessorInterface;
+use crate::vm::processor::{ProcessorInterface, Registers};
+use crate::vm::word::Word;
 
 pub struct SimpleProcessor {
     pub accumulator: i32,
     pub program_counter: usize,
-    pub instruction_register: String,
-    pub opcode: u32,
-    pub operand: String,
+    pub instruction_register: Word,
+    pub opcode: i32,
+    pub operand: usize,
 }
 
 impl SimpleProcessor {
@@ -15,13 +16,19 @@ impl SimpleProcessor {
         SimpleProcessor {
             accumulator: 0,
             program_counter: 0,
-            instruction_register: "".into(),
+            instruction_register: Word::ZERO,
             opcode: 0,
-            operand: "".into(),
+            operand: 0,
         }
     }
 }
 
+impl Default for SimpleProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ProcessorInterface for SimpleProcessor {
     fn increment_pc(&mut self) {
         self.program_counter += 1;
@@ -37,10 +44,9 @@ impl ProcessorInterface for SimpleProcessor {
     }
 
     fn update_state(&mut self, parsed_instr: &ParsedInstruction) -> Result<(), SimpletronError> {
-        self.instruction_register = parsed_instr.data.clone();
-        let raw = parsed_instr.data.parse::<u32>()?;
-        self.opcode = raw / 100;
-        self.operand = (raw % 100).to_string();
+        self.instruction_register = parsed_instr.data;
+        self.opcode = parsed_instr.data.opcode();
+        self.operand = parsed_instr.data.operand();
         Ok(())
     }
 
@@ -60,4 +66,14 @@ impl ProcessorInterface for SimpleProcessor {
     fn get_pc(&self) -> usize {
         self.program_counter
     }
+
+    fn registers(&self) -> Registers {
+        Registers {
+            accumulator: self.accumulator,
+            program_counter: self.program_counter,
+            instruction_register: self.instruction_register,
+            opcode: self.opcode,
+            operand: self.operand,
+        }
+    }
 }
@@ -0,0 +1,185 @@
+//! What each operation does when it runs. Every [`OpcodeSpec`] points at
+//! one of these functions, or at one of its instruction set's own, and the
+//! machine runs whatever its table says.
+//!
+//! [`OpcodeSpec`]: crate::vm::isa::OpcodeSpec
+
+use crate::vm::error::SimpletronError;
+use crate::vm::machine::Output;
+
+/// The machine as an instruction sees it.
+pub trait Execution {
+    fn acc(&self) -> i32;
+    fn set_acc(&mut self, value: i32);
+    fn read_cell(&self, address: usize) -> Result<i32, SimpletronError>;
+    fn write_cell(&mut self, address: usize, value: i32) -> Result<(), SimpletronError>;
+
+    /// The next input value, fitted to a word. The machine waits for one
+    /// before running an instruction whose spec reads input, and only
+    /// those may call this.
+    fn next_input(&mut self) -> Result<i32, SimpletronError>;
+    fn write_output(&mut self, output: Output);
+
+    /// Fits an exact result to a word with the machine's overflow policy.
+    fn fit(&self, value: i64) -> Result<i32, SimpletronError>;
+}
+
+/// Where execution goes after an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// On to the next instruction.
+    Next,
+    /// To this address.
+    Jump(usize),
+    /// Stop with this exit status.
+    Halt(u8),
+}
+
+/// Runs an instruction with its operand.
+pub type Semantics = fn(&mut dyn Execution, usize) -> Result<Effect, SimpletronError>;
+
+pub fn read(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    let value = machine.next_input()?;
+    machine.write_cell(operand, value)?;
+    Ok(Effect::Next)
+}
+
+pub fn read_i(machine: &mut dyn Execution, _: usize) -> Result<Effect, SimpletronError> {
+    let value = machine.next_input()?;
+    machine.set_acc(value);
+    Ok(Effect::Next)
+}
+
+pub fn write(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    let value = machine.read_cell(operand)?;
+    machine.write_output(Output::Memory {
+        address: operand,
+        value,
+    });
+    Ok(Effect::Next)
+}
+
+pub fn write_acc(machine: &mut dyn Execution, _: usize) -> Result<Effect, SimpletronError> {
+    machine.write_output(Output::Accumulator(machine.acc()));
+    Ok(Effect::Next)
+}
+
+pub fn load_m(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    let value = machine.read_cell(operand)?;
+    machine.set_acc(value);
+    Ok(Effect::Next)
+}
+
+pub fn store(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    machine.write_cell(operand, machine.acc())?;
+    Ok(Effect::Next)
+}
+
+pub fn load_i(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    machine.set_acc(operand as i32);
+    Ok(Effect::Next)
+}
+
+pub fn add_m(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    let value = machine.read_cell(operand)?;
+    arithmetic(machine, value, |acc, value| Some(acc + value))
+}
+
+pub fn sub_m(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    let value = machine.read_cell(operand)?;
+    arithmetic(machine, value, |acc, value| Some(acc - value))
+}
+
+pub fn div_m(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    let value = machine.read_cell(operand)?;
+    arithmetic(machine, value, i64::checked_div)
+}
+
+pub fn mod_m(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    let value = machine.read_cell(operand)?;
+    arithmetic(machine, value, i64::checked_rem)
+}
+
+pub fn mul_m(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    let value = machine.read_cell(operand)?;
+    arithmetic(machine, value, |acc, value| Some(acc * value))
+}
+
+pub fn add_i(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    arithmetic(machine, operand as i32, |acc, value| Some(acc + value))
+}
+
+pub fn sub_i(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    arithmetic(machine, operand as i32, |acc, value| Some(acc - value))
+}
+
+pub fn div_i(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    arithmetic(machine, operand as i32, i64::checked_div)
+}
+
+pub fn mod_i(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    arithmetic(machine, operand as i32, i64::checked_rem)
+}
+
+pub fn mul_i(machine: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    arithmetic(machine, operand as i32, |acc, value| Some(acc * value))
+}
+
+pub fn jump(_: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    Ok(Effect::Jump(operand))
+}
+
+pub fn jump_if_negative(
+    machine: &mut dyn Execution,
+    operand: usize,
+) -> Result<Effect, SimpletronError> {
+    Ok(branch(machine.acc() < 0, operand))
+}
+
+pub fn jump_if_zero(
+    machine: &mut dyn Execution,
+    operand: usize,
+) -> Result<Effect, SimpletronError> {
+    Ok(branch(machine.acc() == 0, operand))
+}
+
+pub fn jump_if_not_zero(
+    machine: &mut dyn Execution,
+    operand: usize,
+) -> Result<Effect, SimpletronError> {
+    Ok(branch(machine.acc() != 0, operand))
+}
+
+pub fn jump_if_greater_than_zero(
+    machine: &mut dyn Execution,
+    operand: usize,
+) -> Result<Effect, SimpletronError> {
+    Ok(branch(machine.acc() > 0, operand))
+}
+
+pub fn halt(_: &mut dyn Execution, operand: usize) -> Result<Effect, SimpletronError> {
+    Ok(Effect::Halt(operand as u8))
+}
+
+/// Applies `operation` to the accumulator and `value`. The exact result is
+/// worked out in `i64` and then fitted to a word by the overflow policy;
+/// `None` means the divisor was zero.
+fn arithmetic(
+    machine: &mut dyn Execution,
+    value: i32,
+    operation: fn(i64, i64) -> Option<i64>,
+) -> Result<Effect, SimpletronError> {
+    let result =
+        operation(machine.acc() as i64, value as i64).ok_or(SimpletronError::DivisionByZero)?;
+    let result = machine.fit(result)?;
+    machine.set_acc(result);
+    Ok(Effect::Next)
+}
+
+fn branch(taken: bool, target: usize) -> Effect {
+    if taken {
+        Effect::Jump(target)
+    } else {
+        Effect::Next
+    }
+}
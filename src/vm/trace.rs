@@ -0,0 +1,209 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::vm::error::SimpletronError;
+use crate::vm::fault::FaultReport;
+use crate::vm::instruction::Instruction;
+use crate::vm::machine::Output;
+use crate::vm::memory::MemoryInterface;
+use crate::vm::processor::ProcessorInterface;
+
+/// How much a [`TraceSink`] wants to hear about. Each level includes the
+/// ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TraceLevel {
+    Off,
+    /// Faults and the final halt.
+    Faults,
+    /// Also every value read and written.
+    Io,
+    /// Also every instruction, before and after it runs.
+    Instructions,
+}
+
+impl TraceLevel {
+    pub const ALL: [TraceLevel; 4] = [
+        TraceLevel::Off,
+        TraceLevel::Faults,
+        TraceLevel::Io,
+        TraceLevel::Instructions,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TraceLevel::Off => "off",
+            TraceLevel::Faults => "faults",
+            TraceLevel::Io => "io",
+            TraceLevel::Instructions => "instructions",
+        }
+    }
+}
+
+impl FromStr for TraceLevel {
+    type Err = SimpletronError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        TraceLevel::ALL
+            .into_iter()
+            .find(|level| level.name() == name)
+            .ok_or_else(|| SimpletronError::UnknownTraceLevel(name.to_string()))
+    }
+}
+
+/// Something that happened while a program ran.
+pub enum TraceEvent<'a> {
+    /// The instruction at `address` is about to run.
+    Instruction {
+        address: usize,
+        instruction: Instruction,
+    },
+    /// An instruction ran; the machine is now in this state.
+    Executed {
+        cpu: &'a dyn ProcessorInterface,
+        memory: &'a dyn MemoryInterface,
+    },
+    Input(i32),
+    Output(Output),
+    Fault(&'a FaultReport),
+    Halt(u8),
+}
+
+impl TraceEvent<'_> {
+    pub fn level(&self) -> TraceLevel {
+        match self {
+            TraceEvent::Instruction { .. } | TraceEvent::Executed { .. } => {
+                TraceLevel::Instructions
+            }
+            TraceEvent::Input(_) | TraceEvent::Output(_) => TraceLevel::Io,
+            TraceEvent::Fault(_) | TraceEvent::Halt(_) => TraceLevel::Faults,
+        }
+    }
+}
+
+/// Receives trace events from an [`crate::orchestrator::Orchestrator`].
+///
+/// The orchestrator asks for [`TraceSink::level`] before building an event
+/// and skips it when the level is too low, so a sink whose level is a
+/// constant `Off`, like [`NoTrace`], compiles down to nothing.
+pub trait TraceSink {
+    fn level(&self) -> TraceLevel;
+    fn event(&mut self, event: &TraceEvent<'_>);
+
+    fn enabled(&self, level: TraceLevel) -> bool {
+        self.level() >= level
+    }
+}
+
+/// Traces nothing. The default for an orchestrator.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoTrace;
+
+impl TraceSink for NoTrace {
+    #[inline(always)]
+    fn level(&self) -> TraceLevel {
+        TraceLevel::Off
+    }
+
+    fn event(&mut self, _event: &TraceEvent<'_>) {}
+}
+
+/// Writes one line per event to stderr, so the program's own output on
+/// stdout stays clean.
+#[derive(Debug, Clone, Copy)]
+pub struct PrintTrace {
+    level: TraceLevel,
+}
+
+impl PrintTrace {
+    pub fn new(level: TraceLevel) -> Self {
+        Self { level }
+    }
+}
+
+impl TraceSink for PrintTrace {
+    fn level(&self) -> TraceLevel {
+        self.level
+    }
+
+    fn event(&mut self, event: &TraceEvent<'_>) {
+        match event {
+            TraceEvent::Instruction {
+                address,
+                instruction,
+            } => eprintln!("[TRACE] {:02}: {}", address, describe(instruction)),
+            TraceEvent::Executed { cpu, .. } => eprintln!("[TRACE]     {}", cpu.registers()),
+            TraceEvent::Input(value) => eprintln!("[TRACE] input {}", value),
+            TraceEvent::Output(output) => eprintln!("[TRACE] output {}", output),
+            TraceEvent::Fault(report) => {
+                eprintln!("[TRACE] fault at {:02}: {}", report.address, report.error)
+            }
+            TraceEvent::Halt(status) => eprintln!("[TRACE] halt with status {}", status),
+        }
+    }
+}
+
+/// The interactive `--debug` mode: describes each instruction, dumps the
+/// registers and memory after it and waits for Enter.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StepDebugger;
+
+impl TraceSink for StepDebugger {
+    fn level(&self) -> TraceLevel {
+        TraceLevel::Instructions
+    }
+
+    fn event(&mut self, event: &TraceEvent<'_>) {
+        match event {
+            TraceEvent::Instruction { instruction, .. } => {
+                println!("{}", "-".repeat(100));
+                println!("[DEBUG] {}", describe(instruction));
+            }
+            TraceEvent::Executed { cpu, memory } => {
+                println!();
+                cpu.dump();
+                memory.dump(cpu.get_pc() as isize);
+                println!("\nPress Enter to continue...");
+                let _ = io::stdout().flush();
+                let mut buf = String::new();
+                let _ = io::stdin().read_line(&mut buf);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Trace line for an instruction about to run.
+pub fn describe(instr: &Instruction) -> String {
+    use crate::vm::operation::Opcode::*;
+
+    let operand = instr.operand;
+    match instr.opcode {
+        Read => format!("READ from keyboard -> Memory[+{:0>4}]", operand),
+        ReadI => "READ from keyboard -> ACC".to_string(),
+        Write => format!("WRITE <- Memory[+{:0>4}]", operand),
+        WriteAcc => "Writing ACC value".to_string(),
+        LoadM => format!("ACC <- Memory[+{:0>4}]", operand),
+        Store => format!("ACC -> Memory[+{:0>4}]", operand),
+        LoadI => format!("ACC <- {}", operand),
+        AddM => format!("ACC += value at Memory[+{:0>4}]", operand),
+        SubM => format!("ACC -= value at Memory[+{:0>4}]", operand),
+        MulM => format!("ACC *= value at Memory[+{:0>4}]", operand),
+        DivM => format!("ACC /= value at Memory[+{:0>4}]", operand),
+        ModM => format!("ACC %= value at Memory[+{:0>4}]", operand),
+        AddI => format!("ACC += value {}", operand),
+        SubI => format!("ACC -= value {}", operand),
+        MulI => format!("ACC *= value {}", operand),
+        DivI => format!("ACC /= value {}", operand),
+        ModI => format!("ACC %= value {}", operand),
+        Jump => format!("JUMP -> address Memory[+{:0>4}]", operand),
+        JumpIfNegative => format!("JUMP IF NEG -> address Memory[+{:0>4}]", operand),
+        JumpIfZero => format!("JUMP IF ZERO -> address Memory[+{:0>4}]", operand),
+        JumpIfNotZero => format!("JUMP IF NOT ZERO -> address Memory[+{:0>4}]", operand),
+        JumpIfGreaterThanZero => format!(
+            "JUMP IF GREATER THAN ZERO -> address Memory[+{:0>4}]",
+            operand
+        ),
+        Halt => "HALT".to_string(),
+        Extension(_) => instr.to_string(),
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::vm::error::SimpletronError;
+
+/// The value held by a memory cell or register. Instructions are words too:
+/// `opcode * 100 + operand`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Word(i32);
+
+impl Word {
+    pub const ZERO: Word = Word(0);
+
+    pub const fn new(value: i32) -> Self {
+        Word(value)
+    }
+
+    pub const fn value(self) -> i32 {
+        self.0
+    }
+
+    /// The opcode half of an instruction word.
+    pub const fn opcode(self) -> i32 {
+        self.0 / 100
+    }
+
+    /// The operand half of an instruction word.
+    pub const fn operand(self) -> usize {
+        (self.0 % 100).unsigned_abs() as usize
+    }
+}
+
+impl From<i32> for Word {
+    fn from(value: i32) -> Self {
+        Word(value)
+    }
+}
+
+impl From<u16> for Word {
+    fn from(value: u16) -> Self {
+        Word(value as i32)
+    }
+}
+
+impl From<Word> for i32 {
+    fn from(word: Word) -> Self {
+        word.0
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for Word {
+    type Err = SimpletronError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        text.trim()
+            .parse()
+            .map(Word)
+            .map_err(|_| SimpletronError::InvalidInstruction(text.to_string()))
+    }
+}